@@ -0,0 +1,198 @@
+//! # Account Module
+//!
+//! This module summarizes the account of the current user:
+//! its type, how long premium lasts, and its fidelity points.
+//!
+//! `--warn-days` turns the summary into a check, failing with `Expiring`
+//! when premium expires within that many days, for scheduled runs.
+//!
+//! Fidelity points are only converted above the conversion minimum,
+//! and the premium extension is measured by reading the account again.
+//! The API reports no conversion rate, so dry runs estimate the extension
+//! from the rate of the Real-Debrid fidelity program, which can be overridden.
+
+use serde_json::{Value, json};
+
+use crate::app::Output;
+use crate::date::{self, SECONDS_PER_DAY};
+use crate::error::{ApiError, Failure};
+use crate::prelude::*;
+use crate::{ARGS, settings, user};
+
+/// The fewest points Real-Debrid converts, per the fidelity program.
+pub const POINTS_PER_CONVERSION: u64 = 1000;
+
+/// The premium days gained for every `POINTS_PER_CONVERSION` points.
+pub const DAYS_PER_CONVERSION: f64 = 30.0;
+
+/// The account of the current user.
+struct Account {
+    username: String,
+    /// Like `premium` or `free`.
+    kind: String,
+    /// Seconds of premium left.
+    premium: i64,
+    /// The date premium ends, as `YYYY-MM-DD`.
+    expiration: Option<String>,
+    points: u64,
+}
+impl Account {
+    fn get() -> Result<Self, ApiError> {
+        let user = serde_json::from_str::<Value>(&user::get_user()?).unwrap_or_default();
+
+        Ok(Self {
+            username: user["username"].as_str().unwrap_or_default().to_string(),
+            kind: user["type"].as_str().unwrap_or_default().to_string(),
+            premium: user["premium"].as_i64().unwrap_or(0),
+            expiration: user["expiration"]
+                .as_str()
+                .and_then(date::parse_rfc3339)
+                .map(|seconds| {
+                    let (year, month, day) =
+                        date::civil_from_days(seconds.div_euclid(SECONDS_PER_DAY));
+                    format!("{year:04}-{month:02}-{day:02}")
+                }),
+            points: user["points"].as_u64().unwrap_or(0),
+        })
+    }
+
+    /// Whole days of premium left.
+    fn premium_days(&self) -> i64 {
+        self.premium.max(0) / SECONDS_PER_DAY
+    }
+
+    fn summary(&self) -> String {
+        match ARGS.output() {
+            Output::Text => [
+                format!("username    {}", self.username),
+                format!("type        {}", self.kind),
+                format!("premium     {} days left", self.premium_days()),
+                format!("expiration  {}", self.expiration.as_deref().unwrap_or("-")),
+                format!("points      {}", self.points),
+            ]
+            .join("\n"),
+            Output::Json => self.json().to_string(),
+        }
+    }
+
+    fn json(&self) -> Value {
+        json!({
+            "username": self.username,
+            "type": self.kind,
+            "premium_days": self.premium_days(),
+            "premium_seconds": self.premium,
+            "expiration": self.expiration,
+            "points": self.points,
+        })
+    }
+}
+
+/// Summarizes the account, failing when premium ends within `warn_days`.
+pub fn status(warn_days: Option<u64>) -> ApiResult {
+    let account = Account::get()?;
+
+    if let Some(warn_days) = warn_days
+        && account.premium < warn_days as i64 * SECONDS_PER_DAY
+    {
+        let message = match account.premium {
+            ..=0 => format!("user : `{}` has no premium left", account.username),
+            _ => format!(
+                "user : premium of `{}` expires in {} days, on {}",
+                account.username,
+                account.premium_days(),
+                account.expiration.as_deref().unwrap_or("an unknown date")
+            ),
+        };
+
+        return Err(ApiError::new(Failure::Expiring, message).with_body(account.json().to_string()));
+    }
+
+    Ok(account.summary())
+}
+
+/// Converts the fidelity points into premium, reporting the extension.
+///
+/// Converts nothing unless there are more than `if_above` points, which is
+/// not an error, and refuses below `minimum` points.
+/// `dry_run` only previews the conversion, estimating the extension
+/// as `days` for every `minimum` points.
+pub fn convert_points(minimum: u64, days: f64, if_above: Option<u64>, dry_run: bool) -> ApiResult {
+    let before = Account::get()?;
+
+    if let Some(if_above) = if_above
+        && before.points <= if_above
+    {
+        info!(
+            "convert points : {} points, not above {if_above}",
+            before.points
+        );
+        return Ok(match ARGS.output() {
+            Output::Text => format!(
+                "nothing converted, {} points, not above {if_above}",
+                before.points
+            ),
+            Output::Json => json!({
+                "points_converted": 0,
+                "points_left": before.points,
+                "premium_days": before.premium_days(),
+                "expiration": before.expiration,
+            })
+            .to_string(),
+        });
+    }
+
+    if before.points < minimum {
+        return Err(ApiError::new(
+            Failure::InvalidInput,
+            format!(
+                "convert points : {} points, at least {minimum} are converted",
+                before.points
+            ),
+        ));
+    }
+
+    if dry_run {
+        let extension = (before.points / minimum) as f64 * days;
+        return Ok(match ARGS.output() {
+            Output::Text => format!(
+                "would convert {} points, premium extended by about {extension:.1} days, {} days left",
+                before.points,
+                before.premium_days()
+            ),
+            Output::Json => json!({
+                "points": before.points,
+                "extension_days": extension,
+                "premium_days": before.premium_days(),
+                "dry_run": true,
+            })
+            .to_string(),
+        });
+    }
+
+    settings::convert_points()?;
+    let after = Account::get()?;
+
+    Ok(conversion(&before, &after))
+}
+
+/// The points converted between two readings of the account, and the premium gained.
+fn conversion(before: &Account, after: &Account) -> String {
+    let points = before.points.saturating_sub(after.points);
+    let extension = (after.premium - before.premium).max(0) as f64 / SECONDS_PER_DAY as f64;
+
+    match ARGS.output() {
+        Output::Text => format!(
+            "converted {points} points, premium extended by {extension:.1} days, {} days left until {}",
+            after.premium_days(),
+            after.expiration.as_deref().unwrap_or("-")
+        ),
+        Output::Json => json!({
+            "points_converted": points,
+            "points_left": after.points,
+            "extension_days": extension,
+            "premium_days": after.premium_days(),
+            "expiration": after.expiration,
+        })
+        .to_string(),
+    }
+}
@@ -7,13 +7,17 @@
 
 use std::sync::LazyLock;
 
-use clap::Parser;
+use clap::{ArgAction, Parser, ValueEnum};
+use clap_complete::aot::Shell;
+use clap_complete::engine::ArgValueCompleter;
 use derive_getters::{Dissolve, Getters};
 
+use crate::complete::{download_ids, torrent_ids};
+
 /// A reuseable `'static` variable for argument parsing memoization.
 ///
 /// By using this, you only parse the binary arguments once!
-pub static ARGS: LazyLock<Args> = LazyLock::new(|| Args::parse());
+pub static ARGS: LazyLock<Args> = LazyLock::new(Args::parse);
 
 /// Automatic API command querying
 #[derive(Parser, Clone, Debug, Dissolve, Getters)]
@@ -24,22 +28,82 @@ pub struct Args {
     mode: Mode,
 
     /// Path to the api key
+    ///
+    /// Required by every command calling the API.
     #[arg(short = 'k', long = "key-path")]
-    api_key_path: String,
+    api_key_path: Option<String>,
 
     /// Only print successful information.
     ///
     /// Disable's in-app stderr.
-    #[arg(short, long, default_value_t = false)]
+    #[arg(short, long, default_value_t = false, conflicts_with = "verbose")]
     quiet: bool,
 
+    /// Increase logging verbosity.
+    ///
+    /// `-v` logs info, `-vv` debug and `-vvv` trace records.
+    #[arg(short, long, action = ArgAction::Count)]
+    verbose: u8,
+
     /// Turn off color output.
-    /// 
+    ///
     /// Stdout is already color-free.
-    /// 
-    /// This disables strerr color output.
-    #[arg(short, long, default_value_t = true)]
-    no_color: bool
+    ///
+    /// This disables stderr color output, which is otherwise
+    /// enabled on terminals unless `NO_COLOR` is set.
+    #[arg(short, long, default_value_t = false)]
+    no_color: bool,
+
+    /// Format of the log records.
+    #[arg(long, value_enum, default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
+
+    /// Append log records to this file instead of stderr.
+    #[arg(long)]
+    log_file: Option<String>,
+
+    /// Log every HTTP exchange, with secrets redacted.
+    ///
+    /// Implies `-vvv`.
+    #[arg(long, default_value_t = false, conflicts_with = "quiet")]
+    trace_http: bool,
+
+    /// Record every HTTP exchange into a HAR file, with secrets redacted.
+    #[arg(long)]
+    har: Option<String>,
+
+    /// Path to the job database.
+    ///
+    /// Defaults to `$XDG_DATA_HOME/traffic_cone/jobs.sqlite3`.
+    #[arg(long, value_name = "PATH")]
+    jobs_db: Option<String>,
+
+    /// Output format.
+    ///
+    /// With `json`, the raw error body of a failed API call
+    /// is printed to stdout.
+    #[arg(short, long, value_enum, default_value_t = Output::Text)]
+    output: Output,
+}
+
+/// The format of the printed output
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Output {
+    /// Human readable output
+    #[default]
+    Text,
+    /// Raw JSON output
+    Json,
+}
+
+/// The format of the log records
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// `LEVEL: message` lines
+    #[default]
+    Text,
+    /// One JSON object per line
+    Json,
 }
 
 /// The API method call
@@ -69,6 +133,204 @@ pub enum Mode {
     /// All settings commands
     #[command(subcommand)]
     Settings(Settings),
+    /// Interactive terminal dashboard
+    Tui {
+        /// Seconds between refreshes
+        #[arg(long, default_value_t = 5)]
+        interval: u64,
+    },
+    /// Watch a directory for `.torrent` and `.magnet` files and download their results
+    ///
+    /// Processed inputs are moved into `done/` or `failed/` inside the watched directory.
+    Daemon {
+        /// Directory to watch for `.torrent` and `.magnet` files
+        #[arg(long)]
+        watch: String,
+        /// Directory to download finished torrents into
+        #[arg(long)]
+        out_dir: String,
+        /// Which files of each torrent to select
+        #[arg(long, value_enum, default_value_t = SelectPolicy::All)]
+        select: SelectPolicy,
+        /// Seconds between torrent status checks
+        #[arg(long, default_value_t = 30)]
+        interval: u64,
+    },
+    /// Serve an HTTP API backed by Real-Debrid
+    ///
+    /// By default, this is a REST proxy of the library functions,
+    /// described by `GET /openapi.json`.
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        listen: String,
+        /// Requests handled at once, each streamed file holding one until it is read
+        #[arg(long, value_name = "N", default_value_t = crate::serve::WORKERS)]
+        workers: usize,
+        #[command(flatten)]
+        proxy: ProxyArgs,
+        #[command(flatten)]
+        qbittorrent: QbittorrentArgs,
+        #[command(flatten)]
+        webdav: WebdavArgs,
+    },
+    /// Mount downloaded torrents and downloads as a read-only file system
+    ///
+    /// Files are streamed on demand. Unmount with `fusermount3 -u <dir>` or by interrupting.
+    #[cfg(all(feature = "fuse", target_os = "linux"))]
+    Mount {
+        /// Directory to mount on
+        dir: String,
+        /// Seconds to reuse an unrestricted link for, unless it expires sooner
+        #[arg(long, value_name = "SECONDS", default_value_t = 3600)]
+        link_ttl: u64,
+        /// MiB to fetch at least on every read, serving the following reads
+        #[arg(long, value_name = "MIB", default_value_t = 4, value_parser = clap::value_parser!(u64).range(1..=1024))]
+        read_ahead: u64,
+        /// Requests handled at once, each file being read holding one
+        #[arg(long, value_name = "N", default_value_t = crate::serve::WORKERS)]
+        workers: usize,
+    },
+    /// Persistent jobs of the watch-folder daemon
+    #[command(subcommand)]
+    Jobs(Jobs),
+    /// Print a shell completion script
+    ///
+    /// The script completes torrent and download ids from the API.
+    /// Load it with e.g. `source <(traffic_cone completions bash)`.
+    Completions {
+        /// The shell to complete for
+        shell: Shell,
+    },
+    /// Print the man page
+    Man {
+        /// Write one man page per subcommand into this directory instead
+        #[arg(long)]
+        dir: Option<String>,
+    },
+}
+
+/// Which files of a torrent to select
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SelectPolicy {
+    /// Every file
+    #[default]
+    All,
+    /// Only the largest file
+    Largest,
+    /// Every video file, or the largest file when there are none
+    Video,
+}
+
+/// Options of the REST proxy
+#[derive(clap::Args, Clone, Debug, Getters)]
+pub struct ProxyArgs {
+    /// File listing the bearer tokens accepted from clients, one per line
+    #[arg(long, value_name = "PATH")]
+    token_file: Option<String>,
+    /// Seconds to cache `GET` responses for, 0 to disable caching
+    #[arg(long, value_name = "SECONDS", default_value_t = 30)]
+    cache_ttl: u64,
+    /// Calls to Real-Debrid allowed per minute, shared by every client
+    #[arg(long, value_name = "CALLS", default_value_t = 250)]
+    rate_limit: u32,
+}
+
+/// Options of the WebDAV server
+#[derive(clap::Args, Clone, Debug, Getters)]
+pub struct WebdavArgs {
+    /// Serve downloaded torrents as a read-only WebDAV file tree
+    #[arg(long = "webdav", id = "webdav", conflicts_with = "qbittorrent")]
+    enabled: bool,
+    /// Seconds to reuse an unrestricted link for, unless it expires sooner
+    #[arg(long, value_name = "SECONDS", default_value_t = 3600)]
+    link_ttl: u64,
+}
+
+/// Options of the qBittorrent WebUI API emulation
+#[derive(clap::Args, Clone, Debug, Getters)]
+pub struct QbittorrentArgs {
+    /// Emulate the qBittorrent WebUI API, for Sonarr and Radarr
+    #[arg(long = "qbittorrent", id = "qbittorrent")]
+    enabled: bool,
+    /// Directory holding one folder per category, where finished torrents are downloaded
+    #[arg(long, value_name = "DIR", default_value = "downloads")]
+    download_dir: String,
+    /// Which files of each torrent to select
+    #[arg(long, value_enum, default_value_t = SelectPolicy::All)]
+    select: SelectPolicy,
+    /// Username accepted by `auth/login`
+    #[arg(long, default_value = "admin")]
+    username: String,
+    /// File holding the password accepted by `auth/login`
+    ///
+    /// Without it, no login is required.
+    #[arg(long, value_name = "PATH")]
+    password_file: Option<String>,
+    /// Seconds between torrent status checks
+    #[arg(long, default_value_t = 30)]
+    interval: u64,
+}
+
+/// The state of a job
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JobState {
+    /// Waiting for the torrent to be downloaded on Real-Debrid
+    Submitted,
+    /// Downloading the links of the torrent
+    Downloading,
+    /// Every link is downloaded
+    Done,
+    /// Gave up, see `jobs retry`
+    Failed,
+}
+
+/// All jobs commands
+#[derive(Parser, Clone, Debug)]
+pub enum Jobs {
+    /// List the recorded jobs
+    List {
+        /// Only list jobs in this state
+        #[arg(long, value_enum)]
+        state: Option<JobState>,
+    },
+    /// Submit a magnet link, `.torrent` file or hoster link as a new job
+    ///
+    /// Run it with `jobs resume`, or leave it to a running daemon.
+    /// A hoster link is downloaded into `--out-dir` itself.
+    Add {
+        /// A magnet link, a hoster link, or the path to a `.torrent` file
+        source: String,
+        /// Directory to download the finished torrent into
+        #[arg(long)]
+        out_dir: String,
+        /// Which files of the torrent to select
+        #[arg(long, value_enum, default_value_t = SelectPolicy::All)]
+        select: SelectPolicy,
+    },
+    /// Run unfinished jobs until they are done or failed
+    Resume {
+        /// Only resume this job
+        id: Option<i64>,
+        /// Seconds between torrent status checks
+        #[arg(long, default_value_t = 30)]
+        interval: u64,
+    },
+    /// Run a failed job again
+    ///
+    /// Torrents which failed on Real-Debrid are submitted again.
+    Retry {
+        /// The failed job
+        id: i64,
+        /// Seconds between torrent status checks
+        #[arg(long, default_value_t = 30)]
+        interval: u64,
+    },
+}
+impl From<Jobs> for Mode {
+    fn from(value: Jobs) -> Self {
+        Mode::Jobs(value)
+    }
 }
 
 /// All user commands
@@ -76,6 +338,12 @@ pub enum Mode {
 pub enum User {
     /// Returns information on the current user.
     Json,
+    /// Show the account type, premium time left and fidelity points
+    Status {
+        /// Exit with code 8 when premium expires within this many days, e.g. from cron
+        #[arg(long, value_name = "N")]
+        warn_days: Option<u64>,
+    },
 }
 impl From<User> for Mode {
     fn from(value: User) -> Self {
@@ -89,11 +357,74 @@ pub enum Unrestrict {
     /// Check if a file is downloadable on the concerned hoster.
     Check { link: String },
     /// Unrestrict a hoster link and get a new unrestricted link
-    Link { link: String },
+    ///
+    /// Warns first when the hoster is down, or near its traffic limit.
+    Link {
+        link: String,
+        /// Fail without unrestricting when the hoster is down, exiting with 7
+        #[arg(long)]
+        skip_down: bool,
+        #[command(flatten)]
+        access: LinkAccess,
+    },
+    /// Unrestrict many hoster links, optionally downloading them
+    ///
+    /// Prints one line per link with what became of it, and fails when any is dead or failed.
+    Links {
+        /// Hoster links to unrestrict
+        #[arg(required_unless_present = "from_file")]
+        links: Vec<String>,
+        /// Read the links from a file, one per line, `-` being stdin
+        #[arg(long, value_name = "FILE")]
+        from_file: Option<String>,
+        /// Check every link first, only unrestricting available ones
+        #[arg(long)]
+        check: bool,
+        /// Download the unrestricted links into this directory
+        #[arg(long, value_name = "DIR")]
+        out_dir: Option<String>,
+        /// Links to unrestrict at once
+        #[arg(long, default_value_t = 4)]
+        jobs: usize,
+        /// Skip the links of down hosters, rather than only warning
+        #[arg(long)]
+        skip_down: bool,
+        #[command(flatten)]
+        access: LinkAccess,
+    },
     /// Unrestrict a hoster folder link and get individual links.
     /// 
     /// This returns an empty array if no links found.
-    Folder { link: String },
+    /// With `--expand`, every link is unrestricted, as with `unrestrict links`.
+    #[command(group(
+        clap::ArgGroup::new("folder_access")
+            .args(["password_file", "ask_password", "remote"])
+            .multiple(true)
+            .requires("expand")
+    ))]
+    Folder {
+        link: String,
+        /// Unrestrict every link of the folder
+        #[arg(long)]
+        expand: bool,
+        /// Check every link first, only unrestricting available ones
+        #[arg(long, requires = "expand")]
+        check: bool,
+        /// Download the links into a directory named after the folder, inside this one
+        #[arg(long, value_name = "DIR", requires = "expand")]
+        out_dir: Option<String>,
+        /// Prefix downloaded files with their position in the folder
+        #[arg(long, requires = "out_dir")]
+        numbered: bool,
+        /// Links to unrestrict at once
+        #[arg(long, default_value_t = 4, requires = "expand")]
+        jobs: usize,
+        /// Skip the links of down hosters, rather than only warning
+        #[arg(long, requires = "expand")]
+        skip_down: bool,
+        #[command(flatten)]
+        access: LinkAccess,
+    },
     /// Decrypt a container file (RSDF, CCF, CCF3, DLC)
     ContainerFile,
     /// Decrypt a container file from a link.
@@ -105,13 +436,68 @@ impl From<Unrestrict> for Mode {
     }
 }
 
+/// Options of protected hoster links
+///
+/// The password is never given as an argument, so it stays out of the shell history.
+#[derive(clap::Args, Clone, Debug, Default, Getters)]
+pub struct LinkAccess {
+    /// Read the password of protected links from this file
+    #[arg(long, value_name = "FILE", conflicts_with = "ask_password")]
+    password_file: Option<String>,
+    /// Prompt for the password of protected links
+    #[arg(long)]
+    ask_password: bool,
+    /// Use remote traffic, unrestricting for use from another IP address
+    #[arg(long)]
+    remote: bool,
+}
+
 /// All traffic commands
 #[derive(Parser, Clone, Debug)]
 pub enum Traffic {
     /// Get traffic informations for limited hosters (limits, current usage, extra packages)
     Json,
+    /// List limited hosters, failing when any is near or over its limit
+    ///
+    /// Exits with the hoster unavailable code, 7, naming the hosters at or over `--threshold`.
+    Check {
+        /// Percentage of its limit a hoster may use, e.g. `80%`
+        #[arg(long, value_name = "PERCENT", default_value = "80%", value_parser = crate::quota::parse_percent)]
+        threshold: f64,
+    },
     /// Get traffic details on each hoster used during a defined period
-    Details,
+    ///
+    /// Prints a report, or the raw details with `--output json`.
+    Details {
+        /// First day of the period, as `YYYY-MM-DD`, a week before `--end` by default
+        #[arg(long, value_name = "DATE", value_parser = crate::date::parse_day)]
+        start: Option<String>,
+        /// Last day of the period, as `YYYY-MM-DD`, today by default
+        #[arg(long, value_name = "DATE", value_parser = crate::date::parse_day)]
+        end: Option<String>,
+        /// How to aggregate the usage
+        #[arg(long, value_enum, default_value_t = Aggregate::Day)]
+        by: Aggregate,
+        /// Only show the N rows using the most traffic
+        #[arg(long, value_name = "N")]
+        top: Option<usize>,
+        /// Print CSV, with sizes in bytes
+        #[arg(long)]
+        csv: bool,
+    },
+}
+
+/// How `traffic details` aggregates the usage
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Aggregate {
+    /// One row per host
+    Host,
+    /// One row per day
+    Day,
+    /// One row per ISO week
+    Week,
+    /// Only the total
+    Total,
 }
 impl From<Traffic> for Mode {
     fn from(value: Traffic) -> Self {
@@ -123,9 +509,55 @@ impl From<Traffic> for Mode {
 #[derive(Parser, Clone, Debug)]
 pub enum Streaming {
     /// Get transcoding links for given file, {id} from `downloads` or `unrestrict link`
-    Transcode { id: String },
+    Transcode {
+        /// Download id or filename substring, picked interactively when omitted
+        #[arg(add = ArgValueCompleter::new(download_ids))]
+        id: Option<String>,
+    },
     /// Get detailled media informations for given file, {id} from `downloads` or `unrestrict-link`
-    MediaInfos { id: String },
+    ///
+    /// Prints a summary, or the raw response with `--output json`.
+    MediaInfos {
+        /// Download id or filename substring, picked interactively when omitted
+        #[arg(add = ArgValueCompleter::new(download_ids))]
+        id: Option<String>,
+    },
+    /// Find streamable downloads by audio language or resolution
+    ///
+    /// Requests the media informations of every streamable download.
+    Find {
+        /// Audio language, by name or ISO code, e.g. `English` or `eng`
+        #[arg(long, value_name = "LANG", required_unless_present = "resolution")]
+        audio: Option<String>,
+        /// Resolution of the video, e.g. `720p`, `1080p` or `4k`
+        #[arg(long)]
+        resolution: Option<String>,
+    },
+    /// Play a file through its best transcode, {id} from `downloads` or a hoster link
+    ///
+    /// Prints the stream URL, after the player exits.
+    Play {
+        /// Download id, filename substring or hoster link, picked interactively when omitted
+        #[arg(add = ArgValueCompleter::new(download_ids))]
+        id: Option<String>,
+        /// Transcoding formats to try, in order of preference
+        #[arg(
+            long,
+            value_enum,
+            value_delimiter = ',',
+            default_values_t = StreamFormat::ALL
+        )]
+        format: Vec<StreamFormat>,
+        /// Also write an M3U playlist of the stream, like `stream.m3u8`
+        #[arg(long, value_name = "PATH")]
+        playlist: Option<String>,
+        /// Player command, run with the stream URL as its last argument
+        #[arg(long, default_value = "mpv")]
+        player: String,
+        /// Do not launch the player
+        #[arg(long)]
+        no_play: bool,
+    },
 }
 impl From<Streaming> for Mode {
     fn from(value: Streaming) -> Self {
@@ -133,15 +565,73 @@ impl From<Streaming> for Mode {
     }
 }
 
+/// A transcoding format of Real-Debrid
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StreamFormat {
+    /// HTTP Live Streaming, `.m3u8`
+    Apple,
+    /// MPEG-DASH, `.mpd`
+    Dash,
+    /// Progressive MP4
+    #[value(name = "liveMP4")]
+    LiveMp4,
+    /// H.264 in WebM
+    #[value(name = "h264WebM")]
+    H264WebM,
+}
+
+impl StreamFormat {
+    /// Every format, in the default order of preference.
+    pub const ALL: [StreamFormat; 4] = [
+        StreamFormat::Apple,
+        StreamFormat::Dash,
+        StreamFormat::LiveMp4,
+        StreamFormat::H264WebM,
+    ];
+}
+
+/// The format of `downloads export`
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// One extended M3U playlist per torrent
+    M3u,
+    /// One PLS playlist per torrent
+    Pls,
+    /// One XSPF playlist per torrent
+    Xspf,
+    /// One `.strm` file per file, in a folder per torrent, for Kodi
+    Strm,
+}
+
 /// All download commands
 #[derive(Parser, Clone, Debug)]
 pub enum Download {
     /// Get user downloads list
     Json,
     /// Delete a link from downloads list, returns 204 HTTP code
+    ///
+    /// With filters or `--all`, every matching download is deleted instead.
     Delete {
-        /// Video ID to be deleted
-        id: String,
+        /// Video ID or filename substring to be deleted, picked interactively when omitted
+        #[arg(add = ArgValueCompleter::new(download_ids), conflicts_with_all = BULK_FILTERS)]
+        id: Option<String>,
+        #[command(flatten)]
+        filter: DeleteFilter,
+    },
+    /// Export downloads as playlists or `.strm` files, grouped by torrent
+    ///
+    /// Files of downloaded torrents which are not unrestricted yet are unrestricted.
+    /// Prints the written paths.
+    Export {
+        /// Format to write
+        #[arg(long, value_enum, default_value_t = ExportFormat::M3u)]
+        format: ExportFormat,
+        /// Directory to write into
+        #[arg(long, value_name = "DIR")]
+        out_dir: String,
+        /// Point at streaming transcodes instead of download links
+        #[arg(long)]
+        stream: bool,
     },
 }
 impl From<Download> for Mode {
@@ -150,6 +640,51 @@ impl From<Download> for Mode {
     }
 }
 
+/// The arguments of `DeleteFilter` selecting items, which a single id conflicts with.
+const BULK_FILTERS: [&str; 6] = ["all", "older_than", "name", "host", "min_size", "max_size"];
+
+/// Filters selecting the items of a bulk delete
+///
+/// Every given filter must match for an item to be deleted.
+#[derive(clap::Args, Clone, Debug, Default, Getters)]
+pub struct DeleteFilter {
+    /// Delete every item, unless narrowed by other filters
+    #[arg(long)]
+    all: bool,
+    /// Only delete items added more than this many days ago
+    #[arg(long, value_name = "DAYS")]
+    older_than: Option<u64>,
+    /// Only delete items whose filename matches this glob, e.g. `*.mkv`
+    #[arg(long, value_name = "GLOB")]
+    name: Option<String>,
+    /// Only delete items from this host
+    #[arg(long)]
+    host: Option<String>,
+    /// Only delete items of at least this size, e.g. `700MB`
+    #[arg(long, value_name = "SIZE", value_parser = crate::size::parse)]
+    min_size: Option<u64>,
+    /// Only delete items of at most this size, e.g. `4GiB`
+    #[arg(long, value_name = "SIZE", value_parser = crate::size::parse)]
+    max_size: Option<u64>,
+    /// List what would be deleted without deleting anything, with a filter or `--all`
+    #[arg(long)]
+    dry_run: bool,
+    /// Delete without asking for confirmation
+    #[arg(short, long)]
+    yes: bool,
+}
+impl DeleteFilter {
+    /// Whether this selects items in bulk.
+    pub fn is_bulk(&self) -> bool {
+        self.all
+            || self.older_than.is_some()
+            || self.name.is_some()
+            || self.host.is_some()
+            || self.min_size.is_some()
+            || self.max_size.is_some()
+    }
+}
+
 /// All torrents commands
 #[derive(Parser, Clone, Debug)]
 pub enum Torrents {
@@ -157,7 +692,9 @@ pub enum Torrents {
     Json,
     /// Get all informations on the asked torrent
     Info {
-        id: String,
+        /// Torrent id or filename substring, picked interactively when omitted
+        #[arg(add = ArgValueCompleter::new(torrent_ids))]
+        id: Option<String>,
     },
     /// Get currently active torrents number and the current maximum limit
     ActiveCount,
@@ -165,7 +702,11 @@ pub enum Torrents {
     AvailableHosts,
     /// Add a torrent file to download, return a 201 HTTP code
     AddTorrent {
-        host: String,
+        /// Path to the `.torrent` file
+        file: String,
+        /// Host to upload the torrent to, from `available-hosts`
+        #[arg(long)]
+        host: Option<String>,
     },
     /// Add a magnet link to download, return a 201 HTTP code
     AddMagnet {
@@ -174,12 +715,27 @@ pub enum Torrents {
     },
     /// Select files of a torrent to start it, returns 204 HTTP code
     SelectFiles {
+        /// Torrent id or filename substring
+        #[arg(add = ArgValueCompleter::new(torrent_ids))]
         id: String,
         files: String,
     },
     /// Delete a torrent from torrents list, returns 204 HTTP code
+    ///
+    /// With filters or `--all`, every matching torrent is deleted instead.
     Delete {
-        id: String,
+        /// Torrent id or filename substring, picked interactively when omitted
+        #[arg(
+            add = ArgValueCompleter::new(torrent_ids),
+            conflicts_with_all = BULK_FILTERS,
+            conflicts_with = "status",
+        )]
+        id: Option<String>,
+        /// Only delete torrents with this status, e.g. `downloaded` or `error`
+        #[arg(long)]
+        status: Option<String>,
+        #[command(flatten)]
+        filter: DeleteFilter,
     },
 }
 impl From<Torrents> for Mode {
@@ -193,9 +749,24 @@ impl From<Torrents> for Mode {
 pub enum Hosts {
     /// Get supported hosts
     Json,
+    /// Summarize the status of the hosters, checked by the service
+    ///
+    /// Prints one hoster per line, with its status and when it was checked.
+    Status {
+        /// Only the hosters whose domain or name contains this, e.g. `rapidgator`
+        #[arg(long)]
+        host: Option<String>,
+    },
+    /// Poll the status of the hosters, printing every transition, like `up -> down`
+    Watch {
+        /// Only the hosters whose domain or name contains this, e.g. `rapidgator`
+        #[arg(long)]
+        host: Option<String>,
+        /// Seconds between polls
+        #[arg(long, default_value_t = 300)]
+        interval: u64,
+    },
     /// Get all supported links Regex, useful to find supported links inside a document
-    Status,
-    /// Get all supported folder Regex, useful to find supported links inside a document
     Regex,
     /// Get all supported folder Regex, useful to find supported links inside a document
     RegexFolder,
@@ -213,13 +784,51 @@ impl From<Hosts> for Mode {
 pub enum Settings {
     /// Get current user settings with possible values to update
     Json,
-    /// Update a user setting, returns 204 HTTP code
+    /// Update a user setting, showing its current and new value
+    ///
+    /// The value is checked against the possible values listed by `settings json`.
     Update {
-        setting_name: String,
+        setting_name: SettingName,
         setting_value: String,
+        /// Show the change without updating anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Update every setting of a TOML or JSON file, like `download_port = "secured"`
+    ///
+    /// Every setting is checked before any is updated.
+    Apply {
+        /// The file, read as JSON when its extension is `.json`, as TOML otherwise
+        file: String,
+        /// Show the changes without updating anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Convert fidelity points into premium, reporting the extension
+    ConvertPoints {
+        /// Least points Real-Debrid converts, below which nothing is sent
+        ///
+        /// The API does not report it, so it defaults to the minimum
+        /// of the Real-Debrid fidelity program.
+        #[arg(long, value_name = "POINTS", default_value_t = crate::account::POINTS_PER_CONVERSION, value_parser = clap::value_parser!(u64).range(1..))]
+        minimum: u64,
+        /// Premium days gained for every `--minimum` points, to estimate the extension
+        ///
+        /// The API does not report it, so it defaults to the rate
+        /// of the Real-Debrid fidelity program.
+        #[arg(long, value_name = "DAYS", default_value_t = crate::account::DAYS_PER_CONVERSION)]
+        days: f64,
+        /// Only convert when there are more than this many points, for automation
+        ///
+        /// With fewer points, nothing is converted and the command succeeds.
+        #[arg(long, value_name = "N")]
+        if_above: Option<u64>,
+        /// Show the points that would be converted without converting them
+        ///
+        /// The premium extension is estimated from `--days`.
+        #[arg(long)]
+        dry_run: bool,
     },
-    /// Convert fidelity points, returns 204 HTTP code
-    ConvertPoints,
     /// Send the verification email to change the password, returns 204 HTTP code
     ChangePassword,
     /// Upload a new user avatar image, returns 204 HTTP code
@@ -227,6 +836,45 @@ pub enum Settings {
     /// Reset user avatar image to default, returns 204 HTTP code
     AvatarDelete,
 }
+/// A user setting which can be updated
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+#[value(rename_all = "snake_case")]
+pub enum SettingName {
+    /// The port of download links
+    DownloadPort,
+    /// The language of the website and emails
+    Locale,
+    /// The preferred audio language of streams
+    StreamingLanguagePreference,
+    /// The quality of streams
+    StreamingQuality,
+    /// The quality of streams on mobile
+    MobileStreamingQuality,
+    /// The preferred audio when casting streams
+    StreamingCastAudioPreference,
+}
+impl SettingName {
+    /// The name of the setting in the API.
+    pub fn key(self) -> String {
+        self.to_possible_value()
+            .map(|value| value.get_name().to_string())
+            .unwrap_or_default()
+    }
+
+    /// The field of `settings json` listing the possible values of the setting.
+    pub(crate) fn choices_key(self) -> &'static str {
+        use SettingName::*;
+
+        match self {
+            DownloadPort => "download_ports",
+            Locale => "locales",
+            StreamingLanguagePreference => "streaming_languages",
+            StreamingQuality | MobileStreamingQuality => "streaming_qualities",
+            StreamingCastAudioPreference => "streaming_cast_audio",
+        }
+    }
+}
+
 impl From<Settings> for Mode {
     fn from(value: Settings) -> Self {
         Mode::Settings(value)
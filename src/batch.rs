@@ -0,0 +1,465 @@
+//! # Batch Module
+//!
+//! This module unrestricts many hoster links at once, optionally downloading them.
+//!
+//! Links are given as arguments, or read one per line from a file,
+//! `-` being stdin; empty lines and lines starting with `#` are skipped.
+//!
+//! With `--check`, every link is first checked with `unrestrict check`,
+//! which reports its filename and size without unrestricting it,
+//! so dead links are reported without spending an unrestrict call.
+//!
+//! Every hoster is checked once, warning when it is down or near its
+//! traffic limit, or skipping its links with `--skip-down` when down.
+//!
+//! Links are processed by `jobs` threads, and reported in their given order.
+//! Links downloading to the same file name are told apart by their position,
+//! like `file (3).mkv`, and an error failing every link, like a revoked key,
+//! stops the batch but still reports the links processed so far.
+//! A folder link is expanded into its links, downloaded into a directory
+//! named after the folder, optionally numbered to keep the folder order.
+//!
+//! The returned summary lists one link per line as `state  size  filename  detail`,
+//! or a json array with `--output json`.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use serde_json::{Value, json};
+
+use crate::app::Output;
+use crate::error::{ApiError, Failure};
+use crate::fetch::{self, sanitize_filename};
+use crate::prelude::*;
+use crate::serve::percent_decode;
+use crate::size::humanize;
+use crate::{ARGS, hoster, quota, unrestrict};
+
+/// How the links of a batch are processed.
+#[derive(Default)]
+pub struct Options {
+    /// Check every link before unrestricting it.
+    pub check: bool,
+    /// Links processed at once.
+    pub jobs: usize,
+    /// The directory to download the links into, if any.
+    pub out_dir: Option<PathBuf>,
+    /// Prefix downloaded files with their position in the batch.
+    pub numbered: bool,
+    /// The password of protected links.
+    pub password: Option<String>,
+    /// Use remote traffic.
+    pub remote: bool,
+    /// Skip the links of down hosters, rather than only warning.
+    pub skip_down: bool,
+}
+
+/// What became of a link.
+enum State {
+    /// The check found the file unavailable.
+    Dead(ApiError),
+    /// The hoster is down, and `skip_down` was given.
+    Skipped(ApiError),
+    /// Unrestricting or downloading failed.
+    Failed(ApiError),
+    Unrestricted,
+    Downloaded(PathBuf),
+}
+
+/// A link of the batch.
+struct Outcome {
+    link: String,
+    filename: Option<String>,
+    bytes: Option<u64>,
+    /// The unrestricted link.
+    download: Option<String>,
+    state: State,
+}
+impl Outcome {
+    fn new(link: String, state: State) -> Self {
+        Self {
+            link,
+            filename: None,
+            bytes: None,
+            download: None,
+            state,
+        }
+    }
+
+    fn state(&self) -> &str {
+        match self.state {
+            State::Dead(_) => "dead",
+            State::Skipped(_) => "skipped",
+            State::Failed(_) => "failed",
+            State::Unrestricted => "unrestricted",
+            State::Downloaded(_) => "downloaded",
+        }
+    }
+
+    fn detail(&self) -> String {
+        match &self.state {
+            State::Dead(e) | State::Skipped(e) | State::Failed(e) => e.to_string(),
+            State::Unrestricted => self.download.clone().unwrap_or_default(),
+            State::Downloaded(path) => path.display().to_string(),
+        }
+    }
+
+    fn line(&self) -> String {
+        format!(
+            "{}\t{:>10}\t{}\t{}",
+            self.state(),
+            self.bytes
+                .map(humanize)
+                .unwrap_or_else(|| String::from("-")),
+            self.filename.as_deref().unwrap_or(&self.link),
+            self.detail()
+        )
+    }
+
+    fn json(&self) -> Value {
+        json!({
+            "link": self.link,
+            "state": self.state(),
+            "filename": self.filename,
+            "bytes": self.bytes,
+            "download": self.download,
+            "path": match &self.state {
+                State::Downloaded(path) => Some(path.display().to_string()),
+                _ => None,
+            },
+            "error": match &self.state {
+                State::Dead(e) | State::Skipped(e) | State::Failed(e) => Some(e.to_string()),
+                _ => None,
+            },
+        })
+    }
+}
+
+/// Reads the links of a file, `-` being stdin.
+pub fn read_links(file: &str) -> Result<Vec<String>, ApiError> {
+    let content = match file {
+        "-" => {
+            let mut content = String::new();
+            std::io::stdin()
+                .read_to_string(&mut content)
+                .map(|_| content)
+        }
+        file => fs::read_to_string(file),
+    }
+    .map_err(|e| ApiError::new(Failure::InvalidInput, format!("links `{file}` : {e}")))?;
+
+    Ok(content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect())
+}
+
+/// Unrestricts every link of a folder link, downloading them into
+/// a directory named after the folder inside `out_dir`, if given.
+///
+/// With `numbered`, downloaded files are prefixed with their position in the folder.
+pub fn expand_folder(link: String, mut options: Options) -> ApiResult {
+    let links = serde_json::from_str::<Value>(&unrestrict::folder(link.clone())?)
+        .unwrap_or_default()
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|link| link.as_str().map(String::from))
+        .collect::<Vec<_>>();
+
+    if links.is_empty() {
+        return Err(ApiError::new(
+            Failure::NotFound,
+            format!("unrestrict folder : `{link}` has no links"),
+        ));
+    }
+    info!(
+        "unrestrict folder : `{link}` expanded into {} links",
+        links.len()
+    );
+
+    options.out_dir = options
+        .out_dir
+        .map(|out_dir| out_dir.join(folder_name(&link)));
+    unrestrict(links, &options)
+}
+
+/// Names a folder after the last segment of its link, like `Season 1`
+/// for `https://host.com/folder/Season%201/`.
+fn folder_name(link: &str) -> String {
+    let path = link
+        .split("://")
+        .nth(1)
+        .unwrap_or(link)
+        .split(['?', '#'])
+        .next()
+        .unwrap_or_default();
+    let segment = path
+        .rsplit('/')
+        .find(|segment| !segment.is_empty())
+        .unwrap_or(path);
+
+    sanitize_filename(&percent_decode(segment))
+}
+
+/// Unrestricts every link with `jobs` threads, downloading them into `out_dir` if given.
+///
+/// With `check`, links are checked first and dead ones are skipped.
+/// Every hoster is checked once, and the links of down hosters
+/// are skipped with `skip_down`.
+/// Fails when any link is dead, skipped or failed, after trying every other link.
+pub fn unrestrict(links: Vec<String>, options: &Options) -> ApiResult {
+    if links.is_empty() {
+        return Err(ApiError::new(
+            Failure::InvalidInput,
+            "unrestrict : no links given",
+        ));
+    }
+
+    let statuses = hoster::Statuses::read();
+    let mut down = HashMap::new();
+    for link in &links {
+        let host = hoster::host_of(link);
+        if down.contains_key(&host) {
+            continue;
+        }
+        down.insert(host, statuses.preflight(link, options.skip_down).err());
+    }
+    quota::preflight(links.iter().map(String::as_str));
+
+    let width = links.len().to_string().len();
+    let next = AtomicUsize::new(0);
+    let fatal = Mutex::new(None::<ApiError>);
+    let claimed = Mutex::new(HashSet::new());
+    let outcomes = Mutex::new(links.iter().map(|_| None).collect::<Vec<_>>());
+
+    thread::scope(|scope| {
+        for _ in 0..options.jobs.clamp(1, links.len()) {
+            scope.spawn(|| {
+                loop {
+                    if fatal.lock().unwrap_or_else(|e| e.into_inner()).is_some() {
+                        break;
+                    }
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(link) = links.get(index) else {
+                        break;
+                    };
+
+                    if let Some(Some(e)) = down.get(&hoster::host_of(link)) {
+                        outcomes.lock().unwrap_or_else(|e| e.into_inner())[index] =
+                            Some(Outcome::new(link.clone(), State::Skipped(e.clone())));
+                        continue;
+                    }
+
+                    let prefix = options.numbered.then(|| format!("{:0width$} ", index + 1));
+                    match process(link.clone(), options, prefix, index + 1, &claimed) {
+                        Ok(outcome) => {
+                            outcomes.lock().unwrap_or_else(|e| e.into_inner())[index] =
+                                Some(outcome)
+                        }
+                        Err(e) => {
+                            fatal
+                                .lock()
+                                .unwrap_or_else(|e| e.into_inner())
+                                .get_or_insert(e);
+                        }
+                    }
+                }
+            });
+        }
+    });
+
+    let outcomes = outcomes
+        .into_inner()
+        .unwrap_or_else(|e| e.into_inner())
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+
+    let dead = outcomes
+        .iter()
+        .filter(|outcome| matches!(outcome.state, State::Dead(_)))
+        .count();
+    let skipped = outcomes
+        .iter()
+        .filter(|outcome| matches!(outcome.state, State::Skipped(_)))
+        .count();
+    let failed = outcomes
+        .iter()
+        .filter(|outcome| matches!(outcome.state, State::Failed(_)))
+        .count();
+
+    let summary = match ARGS.output() {
+        Output::Text => outcomes
+            .iter()
+            .map(Outcome::line)
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Output::Json => outcomes
+            .iter()
+            .map(Outcome::json)
+            .collect::<Value>()
+            .to_string(),
+    };
+
+    if let Some(e) = fatal.into_inner().unwrap_or_else(|e| e.into_inner()) {
+        return Err(e.with_body(summary));
+    }
+    let first = outcomes.iter().find_map(|outcome| match &outcome.state {
+        State::Dead(e) | State::Skipped(e) | State::Failed(e) => Some(*e.failure()),
+        _ => None,
+    });
+    match first {
+        None => Ok(summary),
+        Some(failure) => Err(ApiError::new(
+            failure,
+            format!(
+                "unrestrict : {dead} dead, {skipped} skipped and {failed} failed of {} links",
+                outcomes.len()
+            ),
+        )
+        .with_body(summary)),
+    }
+}
+
+/// Checks, unrestricts and downloads a link, prefixing its file name with `prefix`.
+///
+/// The download path is claimed, and suffixed with the `position` of the link
+/// when another link of the batch already claimed it.
+/// Only errors which would fail every other link too are returned.
+fn process(
+    link: String,
+    options: &Options,
+    prefix: Option<String>,
+    position: usize,
+    claimed: &Mutex<HashSet<PathBuf>>,
+) -> Result<Outcome, ApiError> {
+    let mut outcome = Outcome::new(link.clone(), State::Unrestricted);
+
+    if options.check {
+        match checked(&link) {
+            Ok(file) => {
+                outcome.filename = file["filename"].as_str().map(String::from);
+                outcome.bytes = file["filesize"].as_u64();
+            }
+            Err(e) if is_fatal(&e) => return Err(e),
+            Err(e) => {
+                warn!("unrestrict : `{link}` is dead : {e}");
+                outcome.state = State::Dead(e);
+                return Ok(outcome);
+            }
+        }
+    }
+
+    let download =
+        match unrestrict::link_with(link.clone(), options.password.as_deref(), options.remote) {
+            Ok(download) => serde_json::from_str::<Value>(&download).unwrap_or_default(),
+            Err(e) if is_fatal(&e) => return Err(e),
+            Err(e) => {
+                warn!("unrestrict : `{link}` : {e}");
+                outcome.state = State::Failed(e);
+                return Ok(outcome);
+            }
+        };
+    outcome.filename = download["filename"]
+        .as_str()
+        .map(String::from)
+        .or(outcome.filename);
+    outcome.bytes = download["filesize"].as_u64().or(outcome.bytes);
+    outcome.download = download["download"].as_str().map(String::from);
+
+    let (Some(out_dir), Some(url)) = (&options.out_dir, &outcome.download) else {
+        return Ok(outcome);
+    };
+    let filename = outcome.filename.as_deref().unwrap_or("download");
+    let path = out_dir.join(sanitize_filename(&format!(
+        "{}{filename}",
+        prefix.unwrap_or_default()
+    )));
+    let path = match claimed
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(path.clone())
+    {
+        true => path,
+        false => numbered(&path, position),
+    };
+
+    outcome.state = match fetch::download_to(url, &path) {
+        Ok(_) => State::Downloaded(path),
+        Err(e) => {
+            warn!("unrestrict : `{link}` : {e}");
+            State::Failed(e)
+        }
+    };
+
+    Ok(outcome)
+}
+
+/// Suffixes the file name of `path` with `position`, like `file (3).mkv`.
+fn numbered(path: &Path, position: usize) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy())
+        .unwrap_or_default();
+
+    path.with_file_name(match path.extension() {
+        Some(extension) => format!("{stem} ({position}).{}", extension.to_string_lossy()),
+        None => format!("{stem} ({position})"),
+    })
+}
+
+/// Checks a link, treating an unsupported one as dead.
+fn checked(link: &str) -> Result<Value, ApiError> {
+    let file =
+        serde_json::from_str::<Value>(&unrestrict::check(link.to_string())?).unwrap_or_default();
+
+    match file["supported"] == 0 {
+        true => Err(ApiError::new(
+            Failure::HosterUnavailable,
+            format!("unrestrict check : `{link}` is not supported"),
+        )),
+        false => Ok(file),
+    }
+}
+
+/// Whether an error is about the account or the connection rather than the link.
+fn is_fatal(e: &ApiError) -> bool {
+    matches!(
+        e.failure(),
+        Failure::Auth | Failure::Network | Failure::RateLimited
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbered_keeps_the_extension() {
+        assert_eq!(
+            numbered(Path::new("out/file.mkv"), 3),
+            PathBuf::from("out/file (3).mkv")
+        );
+        assert_eq!(
+            numbered(Path::new("out/file"), 12),
+            PathBuf::from("out/file (12)")
+        );
+    }
+
+    #[test]
+    fn folder_names_are_decoded() {
+        assert_eq!(
+            folder_name("https://host.com/folder/Season%201%2B2/?page=1"),
+            "Season 1+2"
+        );
+        assert_eq!(folder_name("https://host.com/folder/abc"), "abc");
+    }
+}
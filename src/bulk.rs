@@ -0,0 +1,269 @@
+//! # Bulk Module
+//!
+//! This module deletes every download or torrent matching a `DeleteFilter`.
+//!
+//! `--dry-run` only lists the matching items.
+//! Otherwise they are logged as warnings and deleted after a confirmation
+//! on the terminal, unless `--yes` is given.
+//!
+//! The returned summary lists one item per line as `id  size  filename`,
+//! or a json array with `--output json`.
+//! When some deletions fail, the summary becomes the error body,
+//! marking which items were deleted.
+
+use std::io::{BufRead, IsTerminal, Write};
+
+use serde_json::{Value, json};
+
+use crate::app::{DeleteFilter, Output};
+use crate::date;
+use crate::error::{ApiError, Failure};
+use crate::size::humanize;
+use crate::{ARGS, ApiResult, downloads, torrents};
+
+/// A deletable download or torrent.
+struct Entry {
+    id: String,
+    filename: String,
+    host: String,
+    bytes: u64,
+    /// Seconds since the unix epoch.
+    added: Option<i64>,
+    status: Option<String>,
+}
+impl Entry {
+    /// Reads a downloads or torrents list entry.
+    ///
+    /// Downloads are dated by `generated` and sized by `filesize`,
+    /// torrents by `added` and `bytes`.
+    fn parse(item: &Value) -> Option<Self> {
+        Some(Self {
+            id: item["id"].as_str()?.to_string(),
+            filename: item["filename"].as_str().unwrap_or_default().to_string(),
+            host: item["host"].as_str().unwrap_or_default().to_string(),
+            bytes: item["filesize"]
+                .as_u64()
+                .or_else(|| item["bytes"].as_u64())
+                .unwrap_or(0),
+            added: item["generated"]
+                .as_str()
+                .or_else(|| item["added"].as_str())
+                .and_then(date::parse_rfc3339),
+            status: item["status"].as_str().map(str::to_string),
+        })
+    }
+
+    fn matches(&self, filter: &DeleteFilter, status: Option<&str>) -> bool {
+        let now = date::now();
+
+        filter.older_than().is_none_or(|days| {
+            self.added
+                .is_some_and(|added| now - added > days as i64 * 86_400)
+        }) && filter
+            .name()
+            .as_ref()
+            .is_none_or(|pattern| glob_match(pattern, &self.filename))
+            && filter
+                .host()
+                .as_ref()
+                .is_none_or(|host| self.host.eq_ignore_ascii_case(host))
+            && filter.min_size().is_none_or(|min| self.bytes >= min)
+            && filter.max_size().is_none_or(|max| self.bytes <= max)
+            && status.is_none_or(|status| {
+                self.status
+                    .as_deref()
+                    .is_some_and(|own| own.eq_ignore_ascii_case(status))
+            })
+    }
+}
+
+/// Deletes every download matching `filter`.
+pub(crate) fn delete_downloads(filter: &DeleteFilter) -> ApiResult {
+    delete_matching(
+        "download",
+        downloads::get_all_downloads()?,
+        filter,
+        None,
+        downloads::delete_download,
+    )
+}
+
+/// Deletes every torrent matching `filter` and `status`.
+pub(crate) fn delete_torrents(filter: &DeleteFilter, status: Option<&str>) -> ApiResult {
+    delete_matching(
+        "torrent",
+        torrents::get_all_torrents()?,
+        filter,
+        status,
+        torrents::delete,
+    )
+}
+
+fn delete_matching(
+    kind: &str,
+    listing: String,
+    filter: &DeleteFilter,
+    status: Option<&str>,
+    delete: impl Fn(String) -> ApiResult,
+) -> ApiResult {
+    let listing = serde_json::from_str::<Value>(&listing).unwrap_or_default();
+    let entries = listing
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Entry::parse)
+        .filter(|entry| entry.matches(filter, status))
+        .collect::<Vec<_>>();
+
+    if entries.is_empty() {
+        info!("bulk delete : no {kind}s match");
+        return Ok(summary(&[], &[]));
+    }
+
+    if *filter.dry_run() {
+        return Ok(summary(&entries, &[]));
+    }
+
+    if !*filter.yes() {
+        let total = entries.iter().map(|entry| entry.bytes).sum::<u64>();
+        warn!(
+            "bulk delete : {} {kind}(s) to delete, {} in total",
+            entries.len(),
+            humanize(total)
+        );
+        for entry in &entries {
+            warn!("bulk delete : {}", line(entry));
+        }
+
+        confirm(&format!("Delete {} {kind}(s)?", entries.len()))?;
+    }
+
+    let mut deleted = Vec::new();
+    let mut failures = Vec::new();
+    for entry in &entries {
+        match delete(entry.id.clone()) {
+            Ok(_) => deleted.push(entry.id.as_str()),
+            Err(e) => {
+                warn!("bulk delete : `{}` ({}) : {e}", entry.filename, entry.id);
+                failures.push(e);
+            }
+        }
+    }
+
+    match failures.first() {
+        None => Ok(summary(&entries, &deleted)),
+        Some(first) => Err(ApiError::new(
+            *first.failure(),
+            format!(
+                "bulk delete : {} {kind}(s) deleted, {} failed, of {}",
+                deleted.len(),
+                failures.len(),
+                entries.len()
+            ),
+        )
+        .with_body(summary(&entries, &deleted))),
+    }
+}
+
+fn line(entry: &Entry) -> String {
+    format!(
+        "{}\t{:>10}\t{}",
+        entry.id,
+        humanize(entry.bytes),
+        entry.filename
+    )
+}
+
+/// Lists the matched entries, marking which of them were deleted.
+fn summary(entries: &[Entry], deleted: &[&str]) -> String {
+    match ARGS.output() {
+        Output::Text => entries.iter().map(line).collect::<Vec<_>>().join("\n"),
+        Output::Json => entries
+            .iter()
+            .map(|entry| {
+                json!({
+                    "id": entry.id,
+                    "filename": entry.filename,
+                    "host": entry.host,
+                    "bytes": entry.bytes,
+                    "deleted": deleted.contains(&entry.id.as_str()),
+                })
+            })
+            .collect::<Value>()
+            .to_string(),
+    }
+}
+
+/// Asks for a yes/no confirmation on the terminal.
+fn confirm(question: &str) -> Result<(), ApiError> {
+    if !std::io::stdin().is_terminal() {
+        return Err(ApiError::new(
+            Failure::InvalidInput,
+            "refusing to delete without a terminal to confirm, see `--yes`",
+        ));
+    }
+
+    eprint!("{question} [y/N] ");
+    let _ = std::io::stderr().flush();
+
+    let mut answer = String::new();
+    let _ = std::io::stdin().lock().read_line(&mut answer);
+
+    match answer.trim().to_lowercase().as_str() {
+        "y" | "yes" => Ok(()),
+        _ => Err(ApiError::new(Failure::InvalidInput, "deletion cancelled")),
+    }
+}
+
+/// Matches `text` against a case-insensitive glob with `*` and `?` wildcards.
+pub(crate) fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.to_lowercase().chars().collect::<Vec<_>>();
+    let text = text.to_lowercase().chars().collect::<Vec<_>>();
+
+    let (mut p, mut t) = (0, 0);
+    // The last `*` seen, and the text position it was tried at.
+    let mut backtrack = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, tried)) => {
+                    p = star + 1;
+                    t = tried + 1;
+                    backtrack = Some((star, tried + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn globs_match_case_insensitively() {
+        assert!(glob_match("*.mkv", "Movie.MKV"));
+        assert!(glob_match("a*b*c", "aXbYbZc"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("*.mkv", "movie.mp4"));
+    }
+
+    #[test]
+    fn question_marks_match_one_character() {
+        assert!(glob_match("ep?.srt", "ep1.srt"));
+        assert!(!glob_match("ep?.srt", "ep10.srt"));
+        assert!(!glob_match("ep?.srt", "ep.srt"));
+    }
+}
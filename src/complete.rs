@@ -0,0 +1,194 @@
+//! # Complete Module
+//!
+//! This module provides shell completions and man pages.
+//!
+//! ## Shell completions
+//!
+//! `completions <SHELL>` prints a registration script which calls back
+//! into this binary, so torrent and download ids can be completed
+//! dynamically from the API.
+//!
+//! Fetched ids are cached for five minutes in `$XDG_CACHE_HOME/traffic_cone`
+//! (or `~/.cache/traffic_cone`), keeping completion responsive.
+//!
+//! The completers run before `ARGS` is parsed, so while completing,
+//! logging and tracing are off, and the API key is read from the
+//! `--key-path` on the command line being completed.
+//!
+//! ## Man pages
+//!
+//! `man` prints the main man page, or writes one page per
+//! subcommand into a directory.
+
+use std::ffi::OsStr;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime};
+
+use clap::CommandFactory;
+use clap_complete::CompleteEnv;
+use clap_complete::aot::Shell;
+use clap_complete::engine::CompletionCandidate;
+use clap_complete::env::{Bash, Elvish, EnvCompleter, Fish, Powershell, Zsh};
+use serde_json::Value;
+
+use crate::app::Args;
+use crate::{ApiResult, downloads, read_api_key, torrents};
+
+/// The environment variable which triggers dynamic completion.
+pub const COMPLETE_VAR: &str = "COMPLETE";
+
+const CACHE_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// How long a request may take while completing.
+pub(crate) const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Whether the binary was called back to complete a command line.
+static COMPLETING: AtomicBool = AtomicBool::new(false);
+
+/// Completes the command line and exits, when called back by the shell.
+///
+/// `CompleteEnv` removes `COMPLETE_VAR` before completing, so it is remembered first.
+pub fn complete() {
+    COMPLETING.store(std::env::var_os(COMPLETE_VAR).is_some(), Ordering::Relaxed);
+
+    CompleteEnv::with_factory(Args::command)
+        .var(COMPLETE_VAR)
+        .complete();
+}
+
+/// Whether a command line is being completed.
+pub(crate) fn is_completing() -> bool {
+    COMPLETING.load(Ordering::Relaxed)
+}
+
+/// The API key of the command line being completed.
+pub(crate) fn api_key() -> Option<String> {
+    read_api_key(&key_path_from_args()?).ok()
+}
+
+/// Completes torrent ids, described by their filenames.
+pub fn torrent_ids(current: &OsStr) -> Vec<CompletionCandidate> {
+    candidates("torrents", torrents::get_all_torrents, current)
+}
+
+/// Completes download ids, described by their filenames.
+pub fn download_ids(current: &OsStr) -> Vec<CompletionCandidate> {
+    candidates("downloads", downloads::get_all_downloads, current)
+}
+
+fn candidates(
+    cache_name: &str,
+    listing: fn() -> ApiResult,
+    current: &OsStr,
+) -> Vec<CompletionCandidate> {
+    let current = current.to_string_lossy();
+
+    let Some(listing) = cached(cache_name).or_else(|| fetch(cache_name, listing)) else {
+        return Vec::new();
+    };
+
+    listing
+        .lines()
+        .filter_map(|line| line.split_once('\t'))
+        .filter(|(id, _)| id.starts_with(current.as_ref()))
+        .map(|(id, filename)| CompletionCandidate::new(id).help(Some(filename.to_string().into())))
+        .collect()
+}
+
+/// The directory holding the completion cache.
+fn cache_dir() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))?;
+
+    Some(base.join("traffic_cone"))
+}
+
+/// Reads a cached listing, unless it has expired.
+fn cached(cache_name: &str) -> Option<String> {
+    let path = cache_dir()?.join(cache_name);
+    let age = SystemTime::now()
+        .duration_since(fs::metadata(&path).ok()?.modified().ok()?)
+        .ok()?;
+
+    (age < CACHE_TTL)
+        .then(|| fs::read_to_string(path).ok())
+        .flatten()
+}
+
+/// Fetches an `id\tfilename` listing from the API and caches it.
+fn fetch(cache_name: &str, listing: fn() -> ApiResult) -> Option<String> {
+    api_key()?;
+
+    let items = serde_json::from_str::<Value>(&listing().ok()?).ok()?;
+    let listing = items
+        .as_array()?
+        .iter()
+        .filter_map(|item| {
+            Some(format!(
+                "{}\t{}\n",
+                item["id"].as_str()?,
+                item["filename"].as_str()?
+            ))
+        })
+        .collect::<String>();
+
+    if let Some(dir) = cache_dir() {
+        let _ = fs::create_dir_all(&dir).and_then(|_| fs::write(dir.join(cache_name), &listing));
+    }
+
+    Some(listing)
+}
+
+/// Finds the `--key-path` value on the command line being completed.
+fn key_path_from_args() -> Option<String> {
+    let mut args = std::env::args().skip_while(|arg| arg != "--");
+
+    while let Some(arg) = args.next() {
+        if arg == "-k" || arg == "--key-path" {
+            return args.next();
+        }
+        if let Some(path) = arg.strip_prefix("--key-path=") {
+            return Some(path.to_string());
+        }
+        if let Some(path) = arg.strip_prefix("-k").filter(|path| !path.is_empty()) {
+            return Some(path.to_string());
+        }
+    }
+
+    None
+}
+
+/// Writes the completion registration script for `shell`.
+pub fn write_completions(shell: Shell, buf: &mut dyn Write) -> io::Result<()> {
+    let completer: &dyn EnvCompleter = match shell {
+        Shell::Bash => &Bash,
+        Shell::Elvish => &Elvish,
+        Shell::Fish => &Fish,
+        Shell::PowerShell => &Powershell,
+        Shell::Zsh => &Zsh,
+        _ => return Err(io::Error::other(format!("unsupported shell `{shell}`"))),
+    };
+
+    let bin = Args::command().get_name().to_string();
+    let path = std::env::current_exe()
+        .map(|path| path.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| bin.clone());
+
+    completer.write_registration(COMPLETE_VAR, &bin, &bin, &path, buf)
+}
+
+/// Prints the main man page, or writes every page into `dir`.
+pub fn write_man(dir: Option<&str>) -> io::Result<()> {
+    match dir {
+        Some(dir) => {
+            fs::create_dir_all(dir)?;
+            clap_mangen::generate_to(Args::command(), dir)
+        }
+        None => clap_mangen::Man::new(Args::command()).render(&mut io::stdout()),
+    }
+}
@@ -0,0 +1,222 @@
+//! # Configure Module
+//!
+//! This module updates the user settings after validating them
+//! against the possible values listed by `settings json`.
+//!
+//! Every update shows the current and new value of each setting,
+//! and settings which already have their new value are not sent.
+//!
+//! `settings apply` reads the settings from a file, as a TOML or JSON table:
+//! ```toml
+//! download_port = "secured"
+//! streaming_quality = "high"
+//! ```
+//! Every setting of the file is validated before any is updated.
+//! When some updates fail, the others are still sent, and the error
+//! lists which settings were applied and which failed.
+
+use std::fs;
+use std::path::Path;
+
+use clap::ValueEnum;
+use serde_json::{Value, json};
+
+use crate::app::{Output, SettingName};
+use crate::error::{ApiError, Failure};
+use crate::prelude::*;
+use crate::{ARGS, settings};
+
+/// A validated change of a setting.
+struct Change {
+    name: SettingName,
+    current: String,
+    new: String,
+}
+impl Change {
+    fn is_noop(&self) -> bool {
+        self.current == self.new
+    }
+}
+
+/// Updates one setting.
+pub fn update(name: SettingName, value: String, dry_run: bool) -> ApiResult {
+    apply_changes(vec![(name, value)], dry_run)
+}
+
+/// Updates every setting of a TOML or JSON file.
+pub fn apply(file: &str, dry_run: bool) -> ApiResult {
+    let content = fs::read_to_string(file)
+        .map_err(|e| ApiError::new(Failure::InvalidInput, format!("settings `{file}` : {e}")))?;
+
+    let table = match Path::new(file)
+        .extension()
+        .and_then(|extension| extension.to_str())
+    {
+        Some("json") => serde_json::from_str::<Value>(&content).map_err(|e| e.to_string()),
+        _ => content
+            .parse::<toml::Table>()
+            .map_err(|e| e.to_string())
+            .and_then(|table| serde_json::to_value(table).map_err(|e| e.to_string())),
+    }
+    .map_err(|e| ApiError::new(Failure::InvalidInput, format!("settings `{file}` : {e}")))?;
+
+    let Value::Object(table) = table else {
+        return Err(ApiError::new(
+            Failure::InvalidInput,
+            format!("settings `{file}` : expected a table of settings"),
+        ));
+    };
+
+    let settings = table
+        .into_iter()
+        .map(|(key, value)| {
+            let name = SettingName::from_str(&key, false).map_err(|_| {
+                let names = SettingName::value_variants()
+                    .iter()
+                    .map(|name| name.key())
+                    .collect::<Vec<_>>();
+                ApiError::new(
+                    Failure::InvalidInput,
+                    format!(
+                        "settings `{file}` : unknown setting `{key}`, expected one of {}",
+                        names.join(", ")
+                    ),
+                )
+            })?;
+            let value = match value {
+                Value::String(value) => value,
+                value => value.to_string(),
+            };
+
+            Ok((name, value))
+        })
+        .collect::<Result<Vec<_>, ApiError>>()?;
+
+    apply_changes(settings, dry_run)
+}
+
+/// Validates every setting, then updates the changed ones,
+/// failing with the first failure once every update was tried.
+fn apply_changes(settings: Vec<(SettingName, String)>, dry_run: bool) -> ApiResult {
+    let current = serde_json::from_str::<Value>(&settings::get_settings()?).unwrap_or_default();
+
+    let changes = settings
+        .into_iter()
+        .map(|(name, new)| validate(&current, name, new))
+        .collect::<Result<Vec<_>, ApiError>>()?;
+
+    if dry_run {
+        return Ok(diff(&changes));
+    }
+
+    let mut applied = Vec::new();
+    let mut failed = Vec::new();
+    let mut first_failure = None;
+    for change in changes.iter().filter(|change| !change.is_noop()) {
+        match settings::update(change.name.key(), change.new.clone()) {
+            Ok(_) => {
+                info!(
+                    "settings : `{}` updated to `{}`",
+                    change.name.key(),
+                    change.new
+                );
+                applied.push(change.name.key());
+            }
+            Err(e) => {
+                warn!("settings : `{}` : {e}", change.name.key());
+                failed.push(change.name.key());
+                first_failure.get_or_insert(*e.failure());
+            }
+        }
+    }
+
+    let Some(failure) = first_failure else {
+        return Ok(diff(&changes));
+    };
+
+    Err(ApiError::new(
+        failure,
+        format!(
+            "settings : applied {}, failed {}",
+            quoted(&applied),
+            quoted(&failed)
+        ),
+    ))
+}
+
+/// Lists setting names like `` `a`, `b` ``, or `none`.
+fn quoted(keys: &[String]) -> String {
+    match keys.is_empty() {
+        true => String::from("none"),
+        false => keys
+            .iter()
+            .map(|key| format!("`{key}`"))
+            .collect::<Vec<_>>()
+            .join(", "),
+    }
+}
+
+/// Checks `new` against the possible values of the setting.
+fn validate(current: &Value, name: SettingName, new: String) -> Result<Change, ApiError> {
+    let choices = match &current[name.choices_key()] {
+        Value::Array(choices) => choices
+            .iter()
+            .filter_map(|choice| choice.as_str().map(String::from))
+            .collect(),
+        Value::Object(choices) => choices.keys().cloned().collect(),
+        _ => Vec::new(),
+    };
+
+    // Without listed choices, the API has the last word.
+    if !choices.is_empty() && !choices.contains(&new) {
+        return Err(ApiError::new(
+            Failure::InvalidInput,
+            format!(
+                "settings : invalid `{}` value `{new}`, expected one of {}",
+                name.key(),
+                choices.join(", ")
+            ),
+        ));
+    }
+
+    Ok(Change {
+        name,
+        current: match &current[name.key()] {
+            Value::String(value) => value.clone(),
+            Value::Null => String::new(),
+            value => value.to_string(),
+        },
+        new,
+    })
+}
+
+/// Lists the changes as `name  current -> new`, or a json array with `--output json`.
+fn diff(changes: &[Change]) -> String {
+    match ARGS.output() {
+        Output::Text => changes
+            .iter()
+            .map(|change| match change.is_noop() {
+                true => format!("{}\t{} (unchanged)", change.name.key(), change.current),
+                false => format!(
+                    "{}\t{} -> {}",
+                    change.name.key(),
+                    change.current,
+                    change.new
+                ),
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Output::Json => changes
+            .iter()
+            .map(|change| {
+                json!({
+                    "name": change.name.key(),
+                    "current": change.current,
+                    "new": change.new,
+                    "changed": !change.is_noop(),
+                })
+            })
+            .collect::<Value>()
+            .to_string(),
+    }
+}
@@ -0,0 +1,227 @@
+//! # Daemon Module
+//!
+//! This module watches a directory for `.torrent` and `.magnet` files
+//! and carries each of them through to local files.
+//!
+//! For every new file in the watched directory, the daemon:
+//! 1. submits it as a new job, see the `jobs` module,
+//! 2. selects the torrent files according to the `SelectPolicy`,
+//! 3. waits until the torrent is `downloaded`,
+//! 4. unrestricts its links and downloads them into
+//!    `<out-dir>/<torrent name>/`,
+//! 5. moves the input file into `done/` or `failed/`.
+//!
+//! New files are detected with inotify and submitted right away,
+//! while a worker thread checks the torrents on an interval and downloads them.
+//! A submission which fails on the network or is rate limited is sent again
+//! by the worker. A `.magnet` file holds the magnet link on its first line.
+//!
+//! Jobs are recorded in the job database, so unfinished jobs
+//! are carried on when the daemon starts again.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, mpsc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use notify::event::{AccessKind, AccessMode, ModifyKind, RenameMode};
+use notify::{EventKind, RecursiveMode, Watcher};
+
+use crate::app::SelectPolicy;
+use crate::error::{ApiError, Failure};
+use crate::jobs::{self, Job, Progress, Source, Store};
+
+const DONE_DIR: &str = "done";
+const FAILED_DIR: &str = "failed";
+
+/// Runs the daemon until the directory watcher stops.
+pub fn run(
+    watch: &Path,
+    out_dir: &Path,
+    policy: SelectPolicy,
+    interval: Duration,
+) -> Result<(), ApiError> {
+    let setup_error = |e: &dyn std::fmt::Display| {
+        ApiError::new(
+            Failure::InvalidInput,
+            format!("daemon : `{}` : {e}", watch.display()),
+        )
+    };
+
+    for dir in [DONE_DIR, FAILED_DIR] {
+        fs::create_dir_all(watch.join(dir)).map_err(|e| setup_error(&e))?;
+    }
+
+    let store = Store::open()?;
+    let worker_store = Store::open()?;
+
+    let (events, received) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(events).map_err(|e| setup_error(&e))?;
+    watcher
+        .watch(watch, RecursiveMode::NonRecursive)
+        .map_err(|e| setup_error(&e))?;
+
+    info!("daemon : watching `{}`", watch.display());
+
+    let jobs = store.unfinished()?;
+    let known = Mutex::new(
+        jobs.iter()
+            .filter_map(|job| job.input().clone())
+            .collect::<HashSet<_>>(),
+    );
+    if !jobs.is_empty() {
+        info!("daemon : resuming {} unfinished job(s)", jobs.len());
+    }
+
+    let existing = fs::read_dir(watch).map_err(|e| setup_error(&e))?;
+
+    thread::scope(|scope| {
+        // The worker stops once `worker` is dropped, when the watcher stops.
+        let (worker, taken) = mpsc::channel();
+        let known = &known;
+        scope.spawn(move || work(worker_store, watch, interval, jobs, taken, known));
+
+        for entry in existing.flatten() {
+            submit(
+                &store,
+                watch,
+                &entry.path(),
+                out_dir,
+                policy,
+                &worker,
+                known,
+            );
+        }
+
+        for event in received {
+            match event {
+                Ok(event) => {
+                    let completed = matches!(
+                        event.kind,
+                        EventKind::Access(AccessKind::Close(AccessMode::Write))
+                            | EventKind::Modify(ModifyKind::Name(
+                                RenameMode::To | RenameMode::Both
+                            ))
+                    );
+                    if completed && let Some(path) = event.paths.last() {
+                        submit(&store, watch, path, out_dir, policy, &worker, known);
+                    }
+                }
+                Err(e) => warn!("daemon : watch : {e}"),
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Advances `jobs` and the ones `taken` from the watcher every `interval`,
+/// moving their input files once they are done or failed.
+fn work(
+    store: Store,
+    watch: &Path,
+    interval: Duration,
+    mut jobs: Vec<Job>,
+    taken: mpsc::Receiver<Job>,
+    known: &Mutex<HashSet<PathBuf>>,
+) {
+    loop {
+        let check = Instant::now() + interval;
+        loop {
+            match taken.recv_timeout(check.saturating_duration_since(Instant::now())) {
+                Ok(job) => jobs.push(job),
+                Err(mpsc::RecvTimeoutError::Timeout) => break,
+                Err(mpsc::RecvTimeoutError::Disconnected) => return,
+            }
+        }
+
+        jobs.retain_mut(|job| {
+            let outcome = match jobs::advance(&store, job) {
+                Progress::Pending => return true,
+                Progress::Done => DONE_DIR,
+                Progress::Failed(reason) => {
+                    error!("daemon : `{}` : {reason}", job.name());
+                    FAILED_DIR
+                }
+            };
+
+            if let Some(input) = job.input() {
+                finish(&store, watch, input, outcome);
+                known
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .remove(input);
+            }
+            false
+        });
+    }
+}
+
+/// Submits a new input file, unless it is already being processed,
+/// and hands its job to the worker.
+fn submit(
+    store: &Store,
+    watch: &Path,
+    path: &Path,
+    out_dir: &Path,
+    policy: SelectPolicy,
+    worker: &mpsc::Sender<Job>,
+    known: &Mutex<HashSet<PathBuf>>,
+) {
+    let extension = path.extension().and_then(|extension| extension.to_str());
+    if !matches!(extension, Some("torrent" | "magnet"))
+        || !path.is_file()
+        || !known
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(path.to_path_buf())
+    {
+        return;
+    }
+
+    let source = match extension {
+        Some("magnet") => fs::read_to_string(path).map(|magnet| {
+            Source::Magnet(magnet.lines().next().unwrap_or_default().trim().to_string())
+        }),
+        _ => fs::read(path).map(Source::TorrentFile),
+    };
+
+    let submitted = source
+        .map_err(|e| ApiError::new(Failure::InvalidInput, e.to_string()))
+        .and_then(|source| jobs::submit(store, source, Some(path), out_dir, policy));
+
+    match submitted {
+        Ok(job) => {
+            info!("daemon : `{}` submitted", path.display());
+            let _ = worker.send(job);
+        }
+        Err(e) => {
+            error!("daemon : `{}` : {e}", path.display());
+            finish(store, watch, path, FAILED_DIR);
+            known.lock().unwrap_or_else(|e| e.into_inner()).remove(path);
+        }
+    }
+}
+
+/// Moves a processed input file into the `done/` or `failed/` directory.
+fn finish(store: &Store, watch: &Path, input: &Path, outcome: &str) {
+    let Some(filename) = input.file_name() else {
+        return;
+    };
+
+    let destination = watch.join(outcome).join(filename);
+    if let Err(e) = fs::rename(input, &destination) {
+        warn!(
+            "daemon : could not move `{}` to `{}` : {e}",
+            input.display(),
+            destination.display()
+        );
+        return;
+    }
+
+    if let Err(e) = store.move_input(input, &destination) {
+        warn!("daemon : {e}");
+    }
+}
@@ -0,0 +1,214 @@
+//! # Date Module
+//!
+//! This module converts between UTC timestamps and civil dates.
+//!
+//! The API reports dates as RFC 3339 strings in UTC,
+//! so no time zone handling is needed.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const SECONDS_PER_DAY: i64 = 86_400;
+
+/// Converts days since the unix epoch into a `(year, month, day)` civil date.
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+/// Formats a point in time as an RFC 3339 UTC timestamp with milliseconds.
+pub fn rfc3339(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs() as i64;
+
+    let (year, month, day) = civil_from_days(seconds.div_euclid(SECONDS_PER_DAY));
+    let time_of_day = seconds.rem_euclid(SECONDS_PER_DAY);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+        time_of_day / 3_600,
+        time_of_day % 3_600 / 60,
+        time_of_day % 60,
+        since_epoch.subsec_millis(),
+    )
+}
+
+/// Converts a `(year, month, day)` civil date into days since the unix epoch.
+pub fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = year - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month = i64::from(month);
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Parses an RFC 3339 timestamp, like `2024-01-31T12:00:00.000Z`,
+/// into seconds since the unix epoch.
+///
+/// A plain `YYYY-MM-DD` date is read as midnight UTC.
+pub fn parse_rfc3339(timestamp: &str) -> Option<i64> {
+    let (date, time) = timestamp
+        .split_once('T')
+        .unwrap_or((timestamp, "00:00:00Z"));
+
+    let mut date = date.splitn(3, '-');
+    let year = date.next()?.parse::<i64>().ok()?;
+    let month = date.next()?.parse::<u32>().ok()?;
+    let day = date.next()?.parse::<u32>().ok()?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    // Split off the offset, which is either `Z` or `+HH:MM` / `-HH:MM`.
+    let offset_at = time.find(['Z', 'z', '+', '-']).unwrap_or(time.len());
+    let (time, offset) = time.split_at(offset_at);
+
+    let mut time = time.split(':');
+    let hours = time.next()?.parse::<i64>().ok()?;
+    let minutes = time.next()?.parse::<i64>().ok()?;
+    let seconds = time
+        .next()
+        .unwrap_or("0")
+        .split('.')
+        .next()?
+        .parse::<i64>()
+        .ok()?;
+
+    let offset = match offset.split_at_checked(1) {
+        Some((sign @ ("+" | "-"), offset)) => {
+            let (offset_hours, offset_minutes) = offset.split_once(':')?;
+            let offset = offset_hours.parse::<i64>().ok()? * 3_600
+                + offset_minutes.parse::<i64>().ok()? * 60;
+            if sign == "+" { offset } else { -offset }
+        }
+        _ => 0,
+    };
+
+    Some(
+        days_from_civil(year, month, day) * SECONDS_PER_DAY
+            + hours * 3_600
+            + minutes * 60
+            + seconds
+            - offset,
+    )
+}
+
+/// Parses a `YYYY-MM-DD` date, as a clap value parser.
+pub fn parse_day(day: &str) -> Result<String, String> {
+    let invalid = || format!("invalid date `{day}`, expected `YYYY-MM-DD`");
+
+    let days = parse_rfc3339(day).ok_or_else(invalid)? / SECONDS_PER_DAY;
+    // Round trip, to reject days like `2024-02-31`.
+    let (year, month, date) = civil_from_days(days);
+    let normalized = format!("{year:04}-{month:02}-{date:02}");
+    if normalized != day {
+        return Err(invalid());
+    }
+
+    Ok(normalized)
+}
+
+/// Shifts a `YYYY-MM-DD` date by `days`, which may be negative.
+pub fn add_days(day: &str, days: i64) -> Option<String> {
+    let (year, month, date) =
+        civil_from_days(parse_rfc3339(day)?.div_euclid(SECONDS_PER_DAY) + days);
+
+    Some(format!("{year:04}-{month:02}-{date:02}"))
+}
+
+/// The ISO 8601 `(year, week)` of a day since the unix epoch.
+pub fn iso_week(days: i64) -> (i64, u32) {
+    // The unix epoch was a Thursday, and ISO weeks start on Monday.
+    let weekday = (days + 3).rem_euclid(7);
+    let thursday = days - weekday + 3;
+    let (year, ..) = civil_from_days(thursday);
+
+    (
+        year,
+        ((thursday - days_from_civil(year, 1, 1)) / 7 + 1) as u32,
+    )
+}
+
+/// The current time in seconds since the unix epoch.
+pub fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+}
+
+/// Formats seconds since the unix epoch as an HTTP date,
+/// like `Wed, 31 Jan 2024 12:00:00 GMT`.
+pub fn http_date(seconds: i64) -> String {
+    const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let days = seconds.div_euclid(SECONDS_PER_DAY);
+    let time_of_day = seconds.rem_euclid(SECONDS_PER_DAY);
+    let (year, month, day) = civil_from_days(days);
+
+    // The unix epoch was a Thursday.
+    format!(
+        "{}, {day:02} {} {year:04} {:02}:{:02}:{:02} GMT",
+        WEEKDAYS[(days + 4).rem_euclid(7) as usize],
+        MONTHS[month as usize - 1],
+        time_of_day / 3_600,
+        time_of_day % 3_600 / 60,
+        time_of_day % 60,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamps_are_parsed() {
+        assert_eq!(parse_rfc3339("1970-01-01T00:00:00Z"), Some(0));
+        assert_eq!(
+            parse_rfc3339("2024-01-31T12:00:00.000+01:00"),
+            Some(1_706_698_800)
+        );
+        assert_eq!(parse_rfc3339("2024-01-31"), Some(1_706_659_200));
+        assert_eq!(parse_rfc3339("2024-13-01"), None);
+        assert_eq!(parse_rfc3339("yesterday"), None);
+    }
+
+    #[test]
+    fn days_are_validated() {
+        assert_eq!(parse_day("2024-02-29"), Ok(String::from("2024-02-29")));
+        assert!(parse_day("2024-02-31").is_err());
+        assert!(parse_day("2024-2-1").is_err());
+    }
+
+    #[test]
+    fn days_are_shifted() {
+        assert_eq!(add_days("2024-03-01", -1).as_deref(), Some("2024-02-29"));
+        assert_eq!(add_days("2024-12-31", 1).as_deref(), Some("2025-01-01"));
+        assert_eq!(add_days("someday", 1), None);
+    }
+
+    #[test]
+    fn iso_weeks_span_years() {
+        // A Friday, in the last week of 2020.
+        assert_eq!(iso_week(days_from_civil(2021, 1, 1)), (2020, 53));
+        // A Monday, in the first week of 2025.
+        assert_eq!(iso_week(days_from_civil(2024, 12, 30)), (2025, 1));
+        assert_eq!(iso_week(days_from_civil(2024, 6, 15)), (2024, 24));
+    }
+}
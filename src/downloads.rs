@@ -1,6 +1,7 @@
 //! # Downloads Module
 //!
-//! This module provides two api calls: `get_downloads()` and `delete_download(id)`.
+//! This module provides three api calls: `get_downloads()`, `get_all_downloads()`
+//! and `delete_download(id)`.
 //!
 //! ## `get_downloads()`
 //!
@@ -8,6 +9,10 @@
 //!
 //! This returns a serialized list of the `Download`s.
 //!
+//! ## `get_all_downloads()`
+//!
+//! This requests every page of the downloads list from the API.
+//!
 //! ## `delete_download(id)`
 //!
 //! This requests that a specific download be deleted.
@@ -19,12 +24,17 @@ const DOWNLOAD_URL: &str = "https://api.real-debrid.com/rest/1.0/downloads";
 const DELETE_DOWNLOAD_URL: &str = "https://api.real-debrid.com/rest/1.0/downloads/delete/";
 
 /// Get the downloads in json form.
-pub fn get_downloads() -> Json {
+pub fn get_downloads() -> ApiResult {
     send(Get(""), DOWNLOAD_URL)
 }
 
+/// Get every page of the downloads in json form.
+pub fn get_all_downloads() -> ApiResult {
+    get_all_pages(DOWNLOAD_URL)
+}
+
 type Id = String;
 /// Delete a specific download by its id.
-pub fn delete_download(id: Id) -> Json {
+pub fn delete_download(id: Id) -> ApiResult {
     send(Delete(""), format!("{DELETE_DOWNLOAD_URL}{id}"))
 }
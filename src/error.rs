@@ -0,0 +1,181 @@
+//! # Error Module
+//!
+//! This module classifies failed API calls.
+//!
+//! Every `ApiError` carries a `Failure` category, and every
+//! category maps to a distinct process exit code.
+//!
+//! ## Exit codes
+//!
+//! | Code | Failure               |
+//! |------|-----------------------|
+//! | 0    | success               |
+//! | 1    | `Api` (uncategorized) |
+//! | 2    | `InvalidInput`        |
+//! | 3    | `Auth`                |
+//! | 4    | `NotFound`            |
+//! | 5    | `RateLimited`         |
+//! | 6    | `Network`             |
+//! | 7    | `HosterUnavailable`   |
+//! | 8    | `Expiring`            |
+//! | 9    | `Io`                  |
+
+use std::fmt::{self, Display};
+
+use derive_getters::Getters;
+use serde_json::Value;
+
+use crate::Json;
+
+/// The category of a failed API call.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Failure {
+    /// The API returned an error that fits no other category.
+    Api,
+    /// The request was malformed or had bad parameter values.
+    InvalidInput,
+    /// The API key is missing, invalid, or lacks permissions.
+    Auth,
+    /// The requested resource does not exist.
+    NotFound,
+    /// Too many requests were sent in a short period.
+    RateLimited,
+    /// The request never received a response.
+    Network,
+    /// The hoster is unsupported, in maintenance, or over its limit.
+    HosterUnavailable,
+    /// Premium expires within the days given to `user status --warn-days`.
+    Expiring,
+    /// A local file could not be read or written.
+    Io,
+}
+impl Failure {
+    /// The process exit code for this failure.
+    pub fn exit_code(self) -> i32 {
+        use Failure::*;
+
+        match self {
+            Api => 1,
+            InvalidInput => 2,
+            Auth => 3,
+            NotFound => 4,
+            RateLimited => 5,
+            Network => 6,
+            HosterUnavailable => 7,
+            Expiring => 8,
+            Io => 9,
+        }
+    }
+
+    /// Categorizes a Real-Debrid `error_code`.
+    fn from_error_code(code: i64) -> Option<Self> {
+        use Failure::*;
+
+        match code {
+            1..=4 | 26 | 28..=30 | 32 => Some(InvalidInput),
+            8..=15 | 22 => Some(Auth),
+            7 | 24 => Some(NotFound),
+            5 | 21 | 34 | 36 => Some(RateLimited),
+            6 | 16..=20 | 23 => Some(HosterUnavailable),
+            _ => None,
+        }
+    }
+
+    /// Categorizes an HTTP status code.
+    fn from_status(status: u16) -> Self {
+        use Failure::*;
+
+        match status {
+            400 => InvalidInput,
+            401 | 403 => Auth,
+            404 => NotFound,
+            429 => RateLimited,
+            _ => Api,
+        }
+    }
+}
+impl Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Failure::*;
+
+        let name = match self {
+            Api => "api",
+            InvalidInput => "invalid input",
+            Auth => "auth",
+            NotFound => "not found",
+            RateLimited => "rate limited",
+            Network => "network",
+            HosterUnavailable => "hoster unavailable",
+            Expiring => "expiring",
+            Io => "io",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+/// A failed API call.
+#[derive(Clone, Debug, Getters)]
+pub struct ApiError {
+    /// The category of the failure.
+    failure: Failure,
+    /// The HTTP status code, if a response was received.
+    status: Option<u16>,
+    /// The raw response body, if a response was received.
+    body: Json,
+    /// A human readable description of the failure.
+    message: String,
+}
+impl ApiError {
+    /// Creates an error that did not come from an HTTP response.
+    pub fn new(failure: Failure, message: impl Into<String>) -> Self {
+        Self {
+            failure,
+            status: None,
+            body: Json::new(),
+            message: message.into(),
+        }
+    }
+
+    /// Attaches a json body, printed with `--output json`.
+    pub(crate) fn with_body(mut self, body: Json) -> Self {
+        self.body = body;
+        self
+    }
+
+    /// Classifies an unsuccessful HTTP response.
+    ///
+    /// The Real-Debrid `error_code` takes precedence over the HTTP status.
+    pub(crate) fn from_response(status: u16, body: Json) -> Self {
+        let parsed = serde_json::from_str::<Value>(&body).unwrap_or_default();
+        let error_code = parsed["error_code"].as_i64();
+        let error = parsed["error"].as_str().unwrap_or("unknown error");
+
+        let failure = error_code
+            .and_then(Failure::from_error_code)
+            .unwrap_or_else(|| Failure::from_status(status));
+
+        let message = match error_code {
+            Some(code) => format!("{error} (error_code {code}, HTTP {status})"),
+            None => format!("{error} (HTTP {status})"),
+        };
+
+        Self {
+            failure,
+            status: Some(status),
+            body,
+            message,
+        }
+    }
+}
+impl Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} : {}", self.failure, self.message)
+    }
+}
+impl std::error::Error for ApiError {}
+impl From<reqwest::Error> for ApiError {
+    fn from(value: reqwest::Error) -> Self {
+        ApiError::new(Failure::Network, value.to_string())
+    }
+}
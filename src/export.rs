@@ -0,0 +1,282 @@
+//! # Export Module
+//!
+//! This module exports the downloads as playlists or Kodi-style `.strm` files,
+//! one group per downloaded torrent, so media centers can play them.
+//!
+//! The files of a torrent are matched with the downloads by their hoster link,
+//! and files not unrestricted yet are unrestricted.
+//! Downloads which belong to no torrent are grouped as `downloads`,
+//! and a torrent with the same name as another group is suffixed with its id.
+//!
+//! Playlists are written as `<dir>/<torrent>.<format>`,
+//! and `.strm` files as `<dir>/<torrent>/<file name>.strm`, keeping
+//! the extension of a file whose name without it is already taken.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_json::{Value, json};
+
+use crate::app::{ExportFormat, Output, StreamFormat};
+use crate::error::{ApiError, Failure};
+use crate::fetch::sanitize_filename;
+use crate::prelude::*;
+use crate::{ARGS, downloads, play, quota, streaming, torrents, unrestrict};
+
+/// The group of the downloads which belong to no torrent.
+const LOOSE_DOWNLOADS: &str = "downloads";
+
+/// A file of a group, with the download it was unrestricted to.
+struct Entry {
+    title: String,
+    download: String,
+    url: String,
+}
+
+/// A downloaded torrent, or the loose downloads.
+struct Group {
+    name: String,
+    entries: Vec<Entry>,
+}
+
+/// Exports every downloaded torrent into `out_dir`.
+///
+/// With `stream`, entries point at the streaming transcode of the file
+/// rather than its download link.
+pub fn export(format: ExportFormat, out_dir: &str, stream: bool) -> ApiResult {
+    let out_dir = Path::new(out_dir);
+    fs::create_dir_all(out_dir).map_err(|e| {
+        ApiError::new(
+            Failure::InvalidInput,
+            format!("export `{}` : {e}", out_dir.display()),
+        )
+    })?;
+
+    let mut groups = groups()?;
+    if stream {
+        for group in &mut groups {
+            group
+                .entries
+                .retain_mut(|entry| match stream_url(&entry.download) {
+                    Ok(url) => {
+                        entry.url = url;
+                        true
+                    }
+                    Err(e) => {
+                        warn!("export : `{}` has no stream : {e}", entry.title);
+                        false
+                    }
+                });
+        }
+    }
+
+    let mut written = Vec::new();
+    for group in groups.iter().filter(|group| !group.entries.is_empty()) {
+        let paths = write(format, out_dir, group)
+            .map_err(|e| ApiError::new(Failure::Api, format!("export `{}` : {e}", group.name)))?;
+        written.extend(paths.into_iter().map(|path| (group, path)));
+    }
+
+    Ok(match ARGS.output() {
+        Output::Text => written
+            .iter()
+            .map(|(_, path)| path.display().to_string())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Output::Json => written
+            .iter()
+            .map(|(group, path)| {
+                json!({
+                    "group": group.name,
+                    "path": path.display().to_string(),
+                    "entries": group.entries.len(),
+                })
+            })
+            .collect::<Value>()
+            .to_string(),
+    })
+}
+
+/// The downloaded torrents with their downloads, then the loose downloads.
+fn groups() -> Result<Vec<Group>, ApiError> {
+    let downloads =
+        serde_json::from_str::<Value>(&downloads::get_all_downloads()?).unwrap_or_default();
+    let mut by_link = downloads
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|download| Some((download["link"].as_str()?.to_string(), download.clone())))
+        .collect::<HashMap<_, _>>();
+
+    let torrents =
+        serde_json::from_str::<Value>(&torrents::get_all_torrents()?).unwrap_or_default();
+
+    let mut groups = Vec::new();
+    let mut names = HashSet::from([String::from(LOOSE_DOWNLOADS)]);
+    for torrent in torrents.as_array().into_iter().flatten() {
+        let Some(id) = torrent["id"].as_str() else {
+            continue;
+        };
+        if torrent["status"] != "downloaded" {
+            continue;
+        }
+
+        let info = serde_json::from_str::<Value>(&torrents::get_torrent_info(id.to_string())?)
+            .unwrap_or_default();
+        let mut name = sanitize_filename(info["filename"].as_str().unwrap_or(id));
+        if !names.insert(name.clone()) {
+            name = format!("{name} ({id})");
+        }
+
+        let links = info["links"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .collect::<Vec<_>>();
+        quota::preflight(
+            links
+                .iter()
+                .copied()
+                .filter(|link| !by_link.contains_key(*link)),
+        );
+
+        let entries = links
+            .into_iter()
+            .filter_map(|link| {
+                let download = match by_link.remove(link) {
+                    Some(download) => download,
+                    None => unrestrict::link(link.to_string())
+                        .map(|download| serde_json::from_str(&download).unwrap_or_default())
+                        .inspect_err(|e| warn!("export : `{link}` : {e}"))
+                        .ok()?,
+                };
+
+                entry(&download)
+            })
+            .collect();
+
+        groups.push(Group { name, entries });
+    }
+
+    let mut loose = by_link.into_values().collect::<Vec<_>>();
+    loose.sort_by(|a, b| a["filename"].as_str().cmp(&b["filename"].as_str()));
+    groups.push(Group {
+        name: String::from(LOOSE_DOWNLOADS),
+        entries: loose.iter().filter_map(entry).collect(),
+    });
+
+    Ok(groups)
+}
+
+fn entry(download: &Value) -> Option<Entry> {
+    Some(Entry {
+        title: download["filename"].as_str()?.to_string(),
+        download: download["id"].as_str()?.to_string(),
+        url: download["download"].as_str()?.to_string(),
+    })
+}
+
+/// The best streaming transcode of a download.
+fn stream_url(download: &str) -> Result<String, ApiError> {
+    let transcodes = serde_json::from_str::<Value>(&streaming::transcode(download.to_string())?)
+        .unwrap_or_default();
+
+    play::best(&transcodes, &StreamFormat::ALL)
+        .map(|(_, _, url)| url)
+        .ok_or_else(|| ApiError::new(Failure::NotFound, "no transcode"))
+}
+
+/// Writes a group, returning the written paths.
+fn write(format: ExportFormat, out_dir: &Path, group: &Group) -> std::io::Result<Vec<PathBuf>> {
+    let playlist = |extension: &str, content: String| {
+        let path = out_dir.join(format!("{}.{extension}", group.name));
+        fs::write(&path, content).map(|()| vec![path])
+    };
+
+    match format {
+        ExportFormat::M3u => playlist("m3u", m3u(group)),
+        ExportFormat::Pls => playlist("pls", pls(group)),
+        ExportFormat::Xspf => playlist("xspf", xspf(group)),
+        ExportFormat::Strm => {
+            let dir = out_dir.join(&group.name);
+            fs::create_dir_all(&dir)?;
+
+            let mut names = HashSet::new();
+            group
+                .entries
+                .iter()
+                .map(|entry| {
+                    let stem = Path::new(&entry.title)
+                        .file_stem()
+                        .map(|stem| sanitize_filename(&stem.to_string_lossy()))
+                        .unwrap_or_else(|| sanitize_filename(&entry.title));
+                    let name = match names.insert(stem.clone()) {
+                        true => stem,
+                        false => {
+                            let name = sanitize_filename(&entry.title);
+                            names.insert(name.clone());
+                            name
+                        }
+                    };
+                    let path = dir.join(format!("{name}.strm"));
+
+                    fs::write(&path, format!("{}\n", entry.url)).map(|()| path)
+                })
+                .collect()
+        }
+    }
+}
+
+fn m3u(group: &Group) -> String {
+    let mut lines = vec![String::from("#EXTM3U"), format!("#PLAYLIST:{}", group.name)];
+    for entry in &group.entries {
+        lines.push(format!("#EXTINF:-1,{}", entry.title));
+        lines.push(entry.url.clone());
+    }
+
+    lines.join("\n") + "\n"
+}
+
+fn pls(group: &Group) -> String {
+    let mut lines = vec![String::from("[playlist]")];
+    for (number, entry) in (1..).zip(&group.entries) {
+        lines.push(format!("File{number}={}", entry.url));
+        lines.push(format!("Title{number}={}", entry.title));
+        lines.push(format!("Length{number}=-1"));
+    }
+    lines.push(format!("NumberOfEntries={}", group.entries.len()));
+    lines.push(String::from("Version=2"));
+
+    lines.join("\n") + "\n"
+}
+
+fn xspf(group: &Group) -> String {
+    let tracks = group
+        .entries
+        .iter()
+        .map(|entry| {
+            format!(
+                "    <track><location>{}</location><title>{}</title><album>{}</album></track>\n",
+                escape(&entry.url),
+                escape(&entry.title),
+                escape(&group.name)
+            )
+        })
+        .collect::<String>();
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n  \
+         <title>{}</title>\n  <trackList>\n{tracks}  </trackList>\n</playlist>\n",
+        escape(&group.name)
+    )
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
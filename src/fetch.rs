@@ -0,0 +1,160 @@
+//! # Fetch Module
+//!
+//! This module downloads unrestricted links to local files.
+//!
+//! Unrestricted links are plain HTTP(S) links to the Real-Debrid
+//! download servers, so they are fetched without the API key.
+//!
+//! Files are written to `<path>.part` and renamed once complete.
+//! An existing `.part` file is resumed with a `Range` request.
+//! When the server finds nothing left to send, the `.part` file is kept
+//! if it has the full size, or deleted and downloaded again.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+use reqwest::StatusCode;
+use reqwest::blocking::{Client as ReqwestClient, Response};
+use reqwest::header::{CONTENT_RANGE, RANGE};
+
+use crate::error::{ApiError, Failure};
+
+/// How many bytes are read from the server before being written at once.
+const COPY_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Memoization of the download `Client`, which never times out.
+pub(crate) static DOWNLOAD_CLIENT: LazyLock<ReqwestClient> = LazyLock::new(|| {
+    ReqwestClient::builder()
+        .timeout(None)
+        .build()
+        .unwrap_or_default()
+});
+
+/// The temporary path of an incomplete download.
+pub fn part_path(path: &Path) -> PathBuf {
+    let mut part = path.as_os_str().to_owned();
+    part.push(".part");
+
+    PathBuf::from(part)
+}
+
+/// Downloads `url` into `path`, returning the number of bytes written.
+///
+/// Missing parent directories are created.
+/// Failing to write the file fails with `Io`, and the connection
+/// dropping halfway with `Network`.
+pub fn download_to(url: &str, path: &Path) -> Result<u64, ApiError> {
+    let io_error = |e: io::Error| {
+        ApiError::new(
+            Failure::Io,
+            format!("download : `{}` : {e}", path.display()),
+        )
+    };
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(io_error)?;
+    }
+
+    let part = part_path(path);
+    let resume_from = fs::metadata(&part)
+        .map(|metadata| metadata.len())
+        .unwrap_or(0);
+
+    let mut request = DOWNLOAD_CLIENT.get(url);
+    if resume_from > 0 {
+        request = request.header(RANGE, format!("bytes={resume_from}-"));
+    }
+
+    let mut response = request.send()?;
+    let resumed = match response.status() {
+        StatusCode::PARTIAL_CONTENT => true,
+        StatusCode::RANGE_NOT_SATISFIABLE if resume_from > 0 => {
+            if full_size(&response) == Some(resume_from) {
+                info!("download : `{}` already complete", path.display());
+                fs::rename(&part, path).map_err(io_error)?;
+                return Ok(resume_from);
+            }
+
+            warn!(
+                "download : `{}` does not match the file, restarting",
+                part.display()
+            );
+            fs::remove_file(&part).map_err(io_error)?;
+            return download_to(url, path);
+        }
+        status if status.is_success() => false,
+        status => {
+            return Err(ApiError::from_response(
+                status.as_u16(),
+                response.text().unwrap_or_default(),
+            ));
+        }
+    };
+
+    info!(
+        "download : `{}` {}",
+        path.display(),
+        if resumed { "resumed" } else { "started" }
+    );
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resumed)
+        .truncate(!resumed)
+        .open(&part)
+        .map_err(io_error)?;
+
+    let mut buffer = vec![0; COPY_BUFFER_SIZE];
+    let mut written = 0;
+    loop {
+        let read = response.read(&mut buffer).map_err(|e| {
+            ApiError::new(
+                Failure::Network,
+                format!("download : `{}` : {e}", path.display()),
+            )
+        })?;
+        if read == 0 {
+            break;
+        }
+        file.write_all(&buffer[..read]).map_err(io_error)?;
+        written += read as u64;
+    }
+    fs::rename(&part, path).map_err(io_error)?;
+
+    Ok(if resumed {
+        resume_from + written
+    } else {
+        written
+    })
+}
+
+/// The full size of the file, from the `Content-Range` of a 416 response, like `bytes */1234`.
+fn full_size(response: &Response) -> Option<u64> {
+    response
+        .headers()
+        .get(CONTENT_RANGE)?
+        .to_str()
+        .ok()?
+        .strip_prefix("bytes */")?
+        .parse()
+        .ok()
+}
+
+/// Replaces characters which are not allowed in file names.
+pub fn sanitize_filename(name: &str) -> String {
+    let name = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' | '\0' => '_',
+            c => c,
+        })
+        .collect::<String>();
+
+    match name.trim() {
+        "" | "." | ".." => String::from("_"),
+        name => name.to_string(),
+    }
+}
@@ -1,5 +1,41 @@
+use std::path::PathBuf;
+
 use crate::app::*;
 use crate::prelude::*;
+use crate::error::{ApiError, Failure};
+use crate::{ARGS, bulk, pick};
+
+/// Prints the outcome of an API call and exits with its matching code.
+///
+/// Failures are reported on stderr, with the raw error body
+/// printed to stdout when `--output json` is given.
+fn respond(response: ApiResult) -> ! {
+    match response {
+        Ok(body) => {
+            println!("{body}");
+
+            exit(0)
+        }
+        Err(e) => {
+            error!("{e}");
+
+            if *ARGS.output() == Output::Json && !e.body().is_empty() {
+                println!("{}", e.body());
+            }
+
+            exit(e.failure().exit_code())
+        }
+    }
+}
+
+/// Exits after a command which prints nothing when it succeeds,
+/// like a server or the daemon, reporting failures like `respond`.
+fn conclude(outcome: Result<(), ApiError>) -> ! {
+    match outcome {
+        Ok(()) => exit(0),
+        Err(e) => respond(Err(e)),
+    }
+}
 
 pub(crate) fn handle_user(entry: User) -> ! {
     use crate::user::*;
@@ -7,11 +43,10 @@ pub(crate) fn handle_user(entry: User) -> ! {
 
     let response_body = match entry {
         Json => get_user(),
+        Status { warn_days } => crate::account::status(warn_days),
     };
 
-    println!("{response_body}");
-
-    exit(0)
+    respond(response_body)
 }
 
 pub(crate) fn handle_unrestrict(entry: Unrestrict) -> ! {
@@ -20,15 +55,90 @@ pub(crate) fn handle_unrestrict(entry: Unrestrict) -> ! {
 
     let response_body = match entry {
         Check { link } => check(link),
-        Link { link: link_ } => link(link_),
-        Folder { link } => folder(link),
+        Link {
+            link,
+            skip_down,
+            access,
+        } => crate::hoster::preflight(&link, skip_down).and_then(|()| {
+            crate::quota::preflight([link.as_str()]);
+            link_with(link, password(&access)?.as_deref(), *access.remote())
+        }),
+        Links {
+            mut links,
+            from_file,
+            check,
+            out_dir,
+            jobs,
+            skip_down,
+            access,
+        } => from_file
+            .map(|file| crate::batch::read_links(&file))
+            .transpose()
+            .and_then(|read| {
+                links.extend(read.into_iter().flatten());
+                let options = crate::batch::Options {
+                    check,
+                    jobs,
+                    out_dir: out_dir.map(PathBuf::from),
+                    password: password(&access)?,
+                    remote: *access.remote(),
+                    skip_down,
+                    ..Default::default()
+                };
+                crate::batch::unrestrict(links, &options)
+            }),
+        Folder {
+            link,
+            expand: false,
+            ..
+        } => folder(link),
+        Folder {
+            link,
+            expand: true,
+            check,
+            out_dir,
+            numbered,
+            jobs,
+            skip_down,
+            access,
+        } => password(&access).and_then(|password| {
+            crate::batch::expand_folder(
+                link,
+                crate::batch::Options {
+                    check,
+                    jobs,
+                    out_dir: out_dir.map(PathBuf::from),
+                    numbered,
+                    password,
+                    remote: *access.remote(),
+                    skip_down,
+                },
+            )
+        }),
         ContainerFile => container_file(),
         ContainerLink { link } => container_link(link),
     };
 
-    println!("{response_body}");
+    respond(response_body)
+}
 
-    exit(0)
+/// Reads the password of protected links from its file, or prompts for it.
+fn password(access: &LinkAccess) -> Result<Option<String>, ApiError> {
+    let password = match (access.password_file(), access.ask_password()) {
+        (Some(file), _) => std::fs::read_to_string(file)
+            .map(|password| password.trim_end_matches(['\n', '\r']).to_string())
+            .map_err(|e| {
+                ApiError::new(Failure::InvalidInput, format!("password `{file}` : {e}"))
+            })?,
+        (None, true) => rpassword::prompt_password("Password: ")
+            .map_err(|e| ApiError::new(Failure::InvalidInput, format!("password : {e}")))?,
+        (None, false) => return Ok(None),
+    };
+
+    match password.is_empty() {
+        true => Err(ApiError::new(Failure::InvalidInput, "password : empty password")),
+        false => Ok(Some(password)),
+    }
 }
 
 pub(crate) fn handle_traffic(entry: Traffic) -> ! {
@@ -37,12 +147,17 @@ pub(crate) fn handle_traffic(entry: Traffic) -> ! {
 
     let response_body = match entry {
         Json => get_traffic(),
-        Details => get_details(),
+        Check { threshold } => crate::quota::check(threshold),
+        Details {
+            start,
+            end,
+            by,
+            top,
+            csv,
+        } => crate::usage::details(start, end, by, top, csv),
     };
 
-    println!("{response_body}");
-
-    exit(0)
+    respond(response_body)
 }
 
 pub(crate) fn handle_streaming(entry: Streaming) -> ! {
@@ -50,13 +165,24 @@ pub(crate) fn handle_streaming(entry: Streaming) -> ! {
     use Streaming::*;
 
     let response_body = match entry {
-        Transcode { id } => transcode(id),
-        MediaInfos { id } => media_infos(id),
+        Transcode { id } => pick::download(id).and_then(transcode),
+        MediaInfos { id } => pick::download(id).and_then(crate::media::media_infos),
+        Find { audio, resolution } => crate::media::find(audio.as_deref(), resolution.as_deref()),
+        Play {
+            id,
+            format,
+            playlist,
+            player,
+            no_play,
+        } => crate::play::play(
+            id,
+            &format,
+            playlist.as_deref(),
+            (!no_play).then_some(player.as_str()),
+        ),
     };
 
-    println!("{response_body}");
-
-    exit(0)
+    respond(response_body)
 }
 
 pub(crate) fn handle_downloads(entry: Download) -> ! {
@@ -65,12 +191,20 @@ pub(crate) fn handle_downloads(entry: Download) -> ! {
 
     let response_body = match entry {
         Json => get_downloads(),
-        Delete { id } => delete_download(id),
+        Delete { filter, .. } if filter.is_bulk() => bulk::delete_downloads(&filter),
+        Delete { filter, .. } if *filter.dry_run() => Err(ApiError::new(
+            Failure::InvalidInput,
+            "downloads delete : `--dry-run` needs a filter or `--all`",
+        )),
+        Delete { id, .. } => pick::download(id).and_then(delete_download),
+        Export {
+            format,
+            out_dir,
+            stream,
+        } => crate::export::export(format, &out_dir, stream),
     };
 
-    println!("{response_body}");
-
-    exit(0)
+    respond(response_body)
 }
 
 pub(crate) fn handle_torrents(entry: Torrents) -> ! {
@@ -79,18 +213,32 @@ pub(crate) fn handle_torrents(entry: Torrents) -> ! {
 
     let response_body = match entry {
         Json => get_torrents(),
-        Info { id } => get_torrent_info(id),
+        Info { id } => pick::torrent(id).and_then(get_torrent_info),
         ActiveCount => get_active_count(),
         AvailableHosts => get_available_hosts(),
-        AddTorrent { host } => add_torrent(host),
+        AddTorrent { file, host } => std::fs::read(&file)
+            .map_err(|e| {
+                ApiError::new(
+                    Failure::InvalidInput,
+                    format!("torrent file : could not read `{file}` : {e}"),
+                )
+            })
+            .and_then(|torrent| add_torrent(torrent, host)),
         AddMagnet { link } => add_magnet(link),
-        SelectFiles { id, files } => select_files(id, files),
-        Delete { id } => delete(id),
+        SelectFiles { id, files } => {
+            pick::torrent(Some(id)).and_then(|id| select_files(id, files))
+        }
+        Delete { filter, status, .. } if filter.is_bulk() || status.is_some() => {
+            bulk::delete_torrents(&filter, status.as_deref())
+        }
+        Delete { filter, .. } if *filter.dry_run() => Err(ApiError::new(
+            Failure::InvalidInput,
+            "torrents delete : `--dry-run` needs a filter, `--status` or `--all`",
+        )),
+        Delete { id, .. } => pick::torrent(id).and_then(delete),
     };
 
-    println!("{response_body}");
-
-    exit(0)
+    respond(response_body)
 }
 
 pub(crate) fn handle_hosts(entry: Hosts) -> ! {
@@ -99,15 +247,18 @@ pub(crate) fn handle_hosts(entry: Hosts) -> ! {
 
     let response_body = match entry {
         Json => get_hosts(),
-        Status => get_status(),
+        Status { host } => crate::hoster::status(host.as_deref()),
+        Watch { host, interval } => crate::hoster::watch(
+            host.as_deref(),
+            std::time::Duration::from_secs(interval.max(1)),
+        )
+        .map(|never| match never {}),
         Regex => get_regex(),
         RegexFolder => get_regex_folder(),
         Domains => get_domains(),
     };
 
-    println!("{response_body}");
-
-    exit(0)
+    respond(response_body)
 }
 
 pub(crate) fn handle_settings(entry: Settings) -> ! {
@@ -119,14 +270,114 @@ pub(crate) fn handle_settings(entry: Settings) -> ! {
         Update {
             setting_name,
             setting_value,
-        } => update(setting_name, setting_value),
-        ConvertPoints => convert_points(),
+            dry_run,
+        } => crate::configure::update(setting_name, setting_value, dry_run),
+        Apply { file, dry_run } => crate::configure::apply(&file, dry_run),
+        ConvertPoints {
+            minimum,
+            days,
+            if_above,
+            dry_run,
+        } => crate::account::convert_points(minimum, days, if_above, dry_run),
         ChangePassword => change_password(),
         AvatarFile => avatar_file(),
         AvatarDelete => avatar_delete(),
     };
 
-    println!("{response_body}");
+    respond(response_body)
+}
+
+pub(crate) fn handle_tui(interval: u64) -> ! {
+    use std::time::Duration;
+
+    if let Err(e) = crate::tui::run(Duration::from_secs(interval.max(1))) {
+        error!("tui : {e}");
+        exit(1)
+    }
+
+    exit(0)
+}
+
+pub(crate) fn handle_daemon(
+    watch: String,
+    out_dir: String,
+    select: SelectPolicy,
+    interval: u64,
+) -> ! {
+    use std::path::Path;
+    use std::time::Duration;
+
+    let interval = Duration::from_secs(interval.max(1));
+    let outcome = crate::daemon::run(Path::new(&watch), Path::new(&out_dir), select, interval);
+
+    conclude(outcome)
+}
+
+pub(crate) fn handle_serve(
+    listen: String,
+    workers: usize,
+    proxy: ProxyArgs,
+    qbittorrent: QbittorrentArgs,
+    webdav: WebdavArgs,
+) -> ! {
+    use crate::serve::{run_proxy, run_qbittorrent, run_webdav};
+
+    let outcome = if *qbittorrent.enabled() {
+        run_qbittorrent(&listen, workers, &qbittorrent)
+    } else if *webdav.enabled() {
+        run_webdav(&listen, workers, &webdav)
+    } else {
+        run_proxy(&listen, workers, &proxy)
+    };
+
+    conclude(outcome)
+}
+
+#[cfg(all(feature = "fuse", target_os = "linux"))]
+pub(crate) fn handle_mount(dir: String, link_ttl: u64, read_ahead: u64, workers: usize) -> ! {
+    let outcome = crate::mount::run(&dir, link_ttl, read_ahead, workers);
+
+    conclude(outcome)
+}
+
+pub(crate) fn handle_jobs(entry: Jobs) -> ! {
+    use crate::jobs::*;
+    use Jobs::*;
+    use std::path::Path;
+    use std::time::Duration;
+
+    let response_body = Store::open().and_then(|store| match entry {
+        List { state } => list(&store, state),
+        Add {
+            source,
+            out_dir,
+            select,
+        } => add_job(&store, &source, Path::new(&out_dir), select),
+        Resume { id, interval } => resume(&store, id, Duration::from_secs(interval.max(1))),
+        Retry { id, interval } => retry(&store, id, Duration::from_secs(interval.max(1))),
+    });
+
+    respond(response_body)
+}
+
+pub(crate) fn handle_completions(shell: clap_complete::aot::Shell) -> ! {
+    use crate::complete::*;
+
+    if let Err(e) = write_completions(shell, &mut std::io::stdout()) {
+        error!("completions : {e}");
+        exit(1)
+    }
+
+    exit(0)
+}
+
+pub(crate) fn handle_man(dir: Option<String>) -> ! {
+    use crate::complete::*;
+
+    if let Err(e) = write_man(dir.as_deref()) {
+        error!("man : {e}");
+        exit(1)
+    }
 
     exit(0)
 }
@@ -143,5 +394,29 @@ pub fn handle_mode(entry: Mode) -> ! {
         Torrents(torrent_command) => handle_torrents(torrent_command),
         Hosts(host_command) => handle_hosts(host_command),
         Settings(setting_command) => handle_settings(setting_command),
+        Tui { interval } => handle_tui(interval),
+        Daemon {
+            watch,
+            out_dir,
+            select,
+            interval,
+        } => handle_daemon(watch, out_dir, select, interval),
+        Serve {
+            listen,
+            workers,
+            proxy,
+            qbittorrent,
+            webdav,
+        } => handle_serve(listen, workers, proxy, qbittorrent, webdav),
+        #[cfg(all(feature = "fuse", target_os = "linux"))]
+        Mount {
+            dir,
+            link_ttl,
+            read_ahead,
+            workers,
+        } => handle_mount(dir, link_ttl, read_ahead, workers),
+        Jobs(jobs_command) => handle_jobs(jobs_command),
+        Completions { shell } => handle_completions(shell),
+        Man { dir } => handle_man(dir),
     }
 }
@@ -0,0 +1,259 @@
+//! # Hoster Module
+//!
+//! This module summarizes the status of the hosters, as checked by the service.
+//!
+//! A hoster is `up`, `down` or `unsupported`, and is named by its domain,
+//! like `rapidgator.net`; `--host` matches part of the domain or of the name.
+//! `hosts watch` polls the statuses and reports every transition,
+//! and unrestricting a link first warns when its hoster is down.
+
+use std::collections::HashMap;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use serde_json::{Value, json};
+
+use crate::app::Output;
+use crate::date;
+use crate::error::{ApiError, Failure};
+use crate::prelude::*;
+use crate::{ARGS, hosts};
+
+/// The status of a hoster.
+struct Hoster {
+    domain: String,
+    name: String,
+    /// Like `up`, `down` or `unsupported`.
+    status: String,
+    /// When the status was last checked, as an RFC 3339 timestamp.
+    checked: String,
+    /// How many competitors have the hoster up, out of how many.
+    competitors: (usize, usize),
+}
+impl Hoster {
+    fn is_down(&self) -> bool {
+        self.status == "down"
+    }
+
+    fn line(&self) -> String {
+        format!(
+            "{}\t{}\t{}\tchecked {}\t{}/{} competitors up",
+            self.domain,
+            self.name,
+            self.status,
+            self.checked,
+            self.competitors.0,
+            self.competitors.1
+        )
+    }
+
+    fn json(&self) -> Value {
+        json!({
+            "domain": self.domain,
+            "name": self.name,
+            "status": self.status,
+            "check_time": self.checked,
+            "competitors_up": self.competitors.0,
+            "competitors": self.competitors.1,
+        })
+    }
+}
+
+/// Reads the hosters of the `hosts/status` response, sorted by domain.
+fn hosters() -> Result<Vec<Hoster>, ApiError> {
+    let statuses = serde_json::from_str::<Value>(&hosts::get_status()?).unwrap_or_default();
+
+    let mut hosters = statuses
+        .as_object()
+        .into_iter()
+        .flatten()
+        .map(|(domain, status)| {
+            let competitors = status["competitors_status"]
+                .as_object()
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>();
+
+            Hoster {
+                domain: domain.to_lowercase(),
+                name: status["name"].as_str().unwrap_or(domain).to_string(),
+                status: status["status"].as_str().unwrap_or("unknown").to_string(),
+                checked: status["check_time"].as_str().unwrap_or("-").to_string(),
+                competitors: (
+                    competitors
+                        .iter()
+                        .filter(|(_, competitor)| competitor["status"] == "up")
+                        .count(),
+                    competitors.len(),
+                ),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    hosters.sort_by(|a, b| a.domain.cmp(&b.domain));
+    Ok(hosters)
+}
+
+/// Whether `hoster` matches `--host`, by part of its domain or name.
+fn matches(hoster: &Hoster, host: Option<&str>) -> bool {
+    host.is_none_or(|host| {
+        let host = host.to_lowercase();
+        hoster.domain.contains(&host) || hoster.name.to_lowercase().contains(&host)
+    })
+}
+
+/// The host of a link, like `rapidgator.net` for `https://rapidgator.net/file/1`.
+pub fn host_of(link: &str) -> String {
+    link.split("://")
+        .nth(1)
+        .unwrap_or(link)
+        .split(['/', '?', '#', ':'])
+        .next()
+        .unwrap_or_default()
+        .to_lowercase()
+}
+
+/// Whether `host` is served by `domain`, subdomains included.
+pub fn serves(domain: &str, host: &str) -> bool {
+    host == domain || host.ends_with(&format!(".{domain}"))
+}
+
+/// Summarizes the status of every hoster, or of those matching `host`.
+pub fn status(host: Option<&str>) -> ApiResult {
+    let hosters = hosters()?
+        .into_iter()
+        .filter(|hoster| matches(hoster, host))
+        .collect::<Vec<_>>();
+
+    if let Some(host) = host
+        && hosters.is_empty()
+    {
+        return Err(ApiError::new(
+            Failure::NotFound,
+            format!("hosts : no hoster matches `{host}`"),
+        ));
+    }
+
+    Ok(match ARGS.output() {
+        Output::Text => hosters
+            .iter()
+            .map(Hoster::line)
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Output::Json => hosters
+            .iter()
+            .map(Hoster::json)
+            .collect::<Value>()
+            .to_string(),
+    })
+}
+
+/// Polls the status of the hosters matching `host` every `interval`,
+/// printing every transition, like `up -> down`.
+///
+/// Failing to read the statuses after the first poll is only logged.
+pub fn watch(host: Option<&str>, interval: Duration) -> Result<!, ApiError> {
+    let mut known = hosters()?
+        .into_iter()
+        .filter(|hoster| matches(hoster, host))
+        .map(|hoster| (hoster.domain, hoster.status))
+        .collect::<HashMap<_, _>>();
+
+    if let Some(host) = host
+        && known.is_empty()
+    {
+        return Err(ApiError::new(
+            Failure::NotFound,
+            format!("hosts : no hoster matches `{host}`"),
+        ));
+    }
+    info!("hosts : watching {} hoster(s)", known.len());
+
+    loop {
+        thread::sleep(interval);
+
+        let hosters = match hosters() {
+            Ok(hosters) => hosters,
+            Err(e) => {
+                warn!("hosts : watch : {e}");
+                continue;
+            }
+        };
+
+        for hoster in hosters.iter().filter(|hoster| matches(hoster, host)) {
+            let previous = known.insert(hoster.domain.clone(), hoster.status.clone());
+            let Some(previous) = previous.filter(|previous| *previous != hoster.status) else {
+                continue;
+            };
+
+            let time = date::rfc3339(SystemTime::now());
+            match ARGS.output() {
+                Output::Text => {
+                    println!("{time}\t{}\t{previous} -> {}", hoster.domain, hoster.status)
+                }
+                Output::Json => println!(
+                    "{}",
+                    json!({
+                        "time": time,
+                        "domain": hoster.domain,
+                        "from": previous,
+                        "to": hoster.status,
+                    })
+                ),
+            }
+        }
+    }
+}
+
+/// Checks whether the hoster of `link` is down, warning about it,
+/// or failing when `skip_down`.
+pub fn preflight(link: &str, skip_down: bool) -> Result<(), ApiError> {
+    Statuses::read().preflight(link, skip_down)
+}
+
+/// The hoster statuses, read once to check many links.
+pub struct Statuses(Vec<Hoster>);
+impl Statuses {
+    /// Reads the statuses.
+    ///
+    /// Failing to read them is not worth stopping for, so it is only logged,
+    /// and every link then passes.
+    pub fn read() -> Self {
+        Self(hosters().unwrap_or_else(|e| {
+            debug!("hosts : preflight skipped : {e}");
+            Vec::new()
+        }))
+    }
+
+    /// Checks whether the hoster of `link` is down, like `preflight`.
+    pub fn preflight(&self, link: &str, skip_down: bool) -> Result<(), ApiError> {
+        let host = host_of(link);
+
+        let Some(hoster) = self
+            .0
+            .iter()
+            .find(|hoster| serves(&hoster.domain, &host))
+            .filter(|hoster| hoster.is_down())
+        else {
+            return Ok(());
+        };
+
+        match skip_down {
+            true => Err(ApiError::new(
+                Failure::HosterUnavailable,
+                format!(
+                    "hosts : {} is down, as checked {}, `{link}` skipped",
+                    hoster.domain, hoster.checked
+                ),
+            )
+            .with_body(hoster.json().to_string())),
+            false => {
+                warn!(
+                    "hosts : {} is down, as checked {}, unrestricting may fail",
+                    hoster.domain, hoster.checked
+                );
+                Ok(())
+            }
+        }
+    }
+}
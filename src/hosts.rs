@@ -8,22 +8,22 @@ const REGEX_URL: &str = "https://api.real-debrid.com/rest/1.0/hosts/regex";
 const REGEX_FOLDER_URL: &str = "https://api.real-debrid.com/rest/1.0/hosts/regexFolder";
 const DOMAINS_URL: &str = "https://api.real-debrid.com/rest/1.0/hosts/domains";
 
-pub fn get_hosts() -> Json {
+pub fn get_hosts() -> ApiResult {
     send(Get(""), HOSTS_URL)
 }
 
-pub fn get_status() -> Json {
+pub fn get_status() -> ApiResult {
     send(Get(""), STATUS_URL)
 }
 
-pub fn get_regex() -> Json {
+pub fn get_regex() -> ApiResult {
     send(Get(""), REGEX_URL)
 }
 
-pub fn get_regex_folder() -> Json {
+pub fn get_regex_folder() -> ApiResult {
     send(Get(""), REGEX_FOLDER_URL)
 }
 
-pub fn get_domains() -> Json {
+pub fn get_domains() -> ApiResult {
     send(Get(""), DOMAINS_URL)
 }
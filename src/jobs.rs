@@ -0,0 +1,916 @@
+//! # Jobs Module
+//!
+//! This module records queued work in a local SQLite database,
+//! so a torrent is carried from submission to local files across restarts.
+//!
+//! A job holds its source (a magnet link or `.torrent` file) and its torrent id.
+//! Once the torrent is downloaded on Real-Debrid, every link of it is recorded
+//! with its unrestricted link and the local path it is downloaded to.
+//! Incomplete files are kept as `.part` files and resumed.
+//!
+//! A job can also hold a single hoster link, which is downloaded
+//! into the output directory without going through a torrent.
+//!
+//! Jobs go through these states:
+//! 1. `submitted` : the torrent is on Real-Debrid, but not downloaded there yet,
+//!    or the hoster link is queued,
+//! 2. `downloading` : the links of the torrent, or the hoster link, are being downloaded,
+//! 3. `done` or `failed`.
+//!
+//! The database is `$XDG_DATA_HOME/traffic_cone/jobs.sqlite3`
+//! (or `~/.local/share/traffic_cone/jobs.sqlite3`), unless `--jobs-db` is given.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, UNIX_EPOCH};
+
+use clap::ValueEnum;
+use derive_getters::Getters;
+use rusqlite::{Connection, OptionalExtension, Row, params};
+use serde_json::{Value, json};
+
+use crate::app::{JobState, Output, SelectPolicy};
+use crate::date;
+use crate::error::{ApiError, Failure};
+use crate::fetch::{download_to, part_path, sanitize_filename};
+use crate::size::humanize;
+use crate::{ARGS, ApiResult, quota, torrents, unrestrict};
+
+const DATABASE_NAME: &str = "jobs.sqlite3";
+
+/// How long to wait for another process holding the database lock.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How many times a finished torrent is downloaded before giving up.
+const MAX_DOWNLOAD_ATTEMPTS: u32 = 3;
+
+/// File extensions picked by `SelectPolicy::Video`.
+const VIDEO_EXTENSIONS: [&str; 9] = [
+    "mkv", "mp4", "avi", "mov", "wmv", "m4v", "webm", "ts", "mpg",
+];
+
+/// Torrent statuses which will never reach `downloaded`.
+const FAILED_STATUSES: [&str; 4] = ["magnet_error", "error", "virus", "dead"];
+
+const SCHEMA: &str = "
+    PRAGMA foreign_keys = ON;
+
+    CREATE TABLE IF NOT EXISTS jobs (
+        id           INTEGER PRIMARY KEY,
+        input        TEXT,
+        magnet       TEXT,
+        torrent_file BLOB,
+        link         TEXT,
+        torrent      TEXT,
+        name         TEXT NOT NULL,
+        state        TEXT NOT NULL,
+        out_dir      TEXT NOT NULL,
+        policy       TEXT NOT NULL,
+        attempts     INTEGER NOT NULL DEFAULT 0,
+        error        TEXT,
+        updated      INTEGER NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS links (
+        job      INTEGER NOT NULL REFERENCES jobs (id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        link     TEXT NOT NULL,
+        download TEXT,
+        path     TEXT,
+        done     INTEGER NOT NULL DEFAULT 0,
+        PRIMARY KEY (job, position)
+    );
+";
+
+/// Columns added after the first schema, with their definition.
+const ADDED_COLUMNS: [(&str, &str); 1] = [("link", "TEXT")];
+
+const JOB_COLUMNS: &str = "id, input, magnet, torrent_file, link, torrent, name, state, out_dir, policy, attempts, error, updated";
+
+type Id = String;
+
+/// What a job was submitted from.
+pub enum Source {
+    Magnet(String),
+    TorrentFile(Vec<u8>),
+    /// A hoster link, downloaded without a torrent.
+    Link(String),
+}
+impl Source {
+    /// Reads a magnet link, a hoster link, or the `.torrent` file at `source`.
+    pub fn read(source: &str) -> Result<Self, ApiError> {
+        if source.starts_with("magnet:") {
+            return Ok(Self::Magnet(source.to_string()));
+        }
+        if source.starts_with("https://") || source.starts_with("http://") {
+            return Ok(Self::Link(source.to_string()));
+        }
+
+        fs::read(source)
+            .map(Self::TorrentFile)
+            .map_err(|e| ApiError::new(Failure::InvalidInput, format!("jobs : `{source}` : {e}")))
+    }
+
+    /// A name for the job until the torrent reports its own.
+    fn name(&self) -> String {
+        match self {
+            Self::Magnet(magnet) => magnet
+                .split(['?', '&'])
+                .find_map(|parameter| parameter.strip_prefix("dn="))
+                .map(|name| name.replace('+', " "))
+                .unwrap_or_else(|| String::from("magnet")),
+            Self::TorrentFile(_) => String::from("torrent"),
+            Self::Link(link) => link
+                .trim_end_matches('/')
+                .rsplit('/')
+                .next()
+                .filter(|name| !name.is_empty() && !name.contains(':'))
+                .unwrap_or("link")
+                .to_string(),
+        }
+    }
+}
+
+/// A recorded torrent or hoster link, and its way to local files.
+#[derive(Getters)]
+pub struct Job {
+    id: i64,
+    /// The watched file this job was submitted from, if any.
+    input: Option<PathBuf>,
+    source: Source,
+    torrent: Option<Id>,
+    name: String,
+    state: JobState,
+    out_dir: PathBuf,
+    policy: SelectPolicy,
+    download_attempts: u32,
+    error: Option<String>,
+    /// Seconds since the unix epoch.
+    updated: i64,
+}
+impl Job {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        let magnet = row.get::<_, Option<String>>("magnet")?;
+        let link = row.get::<_, Option<String>>("link")?;
+        let source = match (magnet, link) {
+            (Some(magnet), _) => Source::Magnet(magnet),
+            (None, Some(link)) => Source::Link(link),
+            (None, None) => Source::TorrentFile(
+                row.get::<_, Option<Vec<u8>>>("torrent_file")?
+                    .unwrap_or_default(),
+            ),
+        };
+
+        Ok(Self {
+            id: row.get("id")?,
+            input: row.get::<_, Option<String>>("input")?.map(PathBuf::from),
+            source,
+            torrent: row.get("torrent")?,
+            name: row.get("name")?,
+            state: parse_value(&row.get::<_, String>("state")?).unwrap_or(JobState::Failed),
+            out_dir: PathBuf::from(row.get::<_, String>("out_dir")?),
+            policy: parse_value(&row.get::<_, String>("policy")?).unwrap_or_default(),
+            download_attempts: row.get("attempts")?,
+            error: row.get("error")?,
+            updated: row.get("updated")?,
+        })
+    }
+
+    pub(crate) fn is_finished(&self) -> bool {
+        matches!(self.state, JobState::Done | JobState::Failed)
+    }
+}
+
+/// A link of a downloaded torrent.
+struct Link {
+    position: usize,
+    link: String,
+    /// The unrestricted link, until it is downloaded or expires.
+    download: Option<String>,
+    path: Option<PathBuf>,
+    done: bool,
+}
+
+/// The outcome of advancing a job.
+pub(crate) enum Progress {
+    Pending,
+    Done,
+    Failed(String),
+}
+
+/// The job database.
+pub struct Store {
+    connection: Connection,
+}
+impl Store {
+    /// Opens the database given by `--jobs-db`, or the default one.
+    pub fn open() -> Result<Self, ApiError> {
+        let path = match ARGS.jobs_db() {
+            Some(path) => PathBuf::from(path),
+            None => data_dir()
+                .ok_or_else(|| {
+                    ApiError::new(
+                        Failure::InvalidInput,
+                        "jobs : no data directory, see `--jobs-db`",
+                    )
+                })?
+                .join(DATABASE_NAME),
+        };
+
+        Self::open_at(&path)
+    }
+
+    /// Opens the database at `path`, creating it if needed.
+    pub fn open_at(path: &Path) -> Result<Self, ApiError> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| {
+                ApiError::new(Failure::Api, format!("jobs : `{}` : {e}", parent.display()))
+            })?;
+        }
+
+        let connection = Connection::open(path).map_err(database_error)?;
+        connection
+            .busy_timeout(BUSY_TIMEOUT)
+            .and_then(|()| connection.execute_batch(SCHEMA))
+            .and_then(|()| add_columns(&connection))
+            .map_err(database_error)?;
+
+        debug!("jobs : opened `{}`", path.display());
+
+        Ok(Self { connection })
+    }
+
+    /// Gets one job.
+    pub fn get(&self, id: i64) -> Result<Job, ApiError> {
+        self.connection
+            .query_row(
+                &format!("SELECT {JOB_COLUMNS} FROM jobs WHERE id = ?1"),
+                [id],
+                Job::from_row,
+            )
+            .optional()
+            .map_err(database_error)?
+            .ok_or_else(|| ApiError::new(Failure::NotFound, format!("jobs : no job {id}")))
+    }
+
+    /// Lists every job, or only those in `state`, oldest first.
+    pub fn list(&self, state: Option<JobState>) -> Result<Vec<Job>, ApiError> {
+        let mut statement = self
+            .connection
+            .prepare(&format!(
+                "SELECT {JOB_COLUMNS} FROM jobs WHERE ?1 IS NULL OR state = ?1 ORDER BY id"
+            ))
+            .map_err(database_error)?;
+
+        statement
+            .query_map([state.map(value_name)], Job::from_row)
+            .and_then(Iterator::collect)
+            .map_err(database_error)
+    }
+
+    /// Lists the jobs which are neither done nor failed.
+    pub fn unfinished(&self) -> Result<Vec<Job>, ApiError> {
+        let mut jobs = self.list(None)?;
+        jobs.retain(|job| !job.is_finished());
+
+        Ok(jobs)
+    }
+
+    /// Records a new job, setting its id.
+    fn insert(&self, job: &mut Job) -> Result<(), ApiError> {
+        let (magnet, torrent_file, link) = match &job.source {
+            Source::Magnet(magnet) => (Some(magnet.as_str()), None, None),
+            Source::TorrentFile(torrent_file) => (None, Some(torrent_file.as_slice()), None),
+            Source::Link(link) => (None, None, Some(link.as_str())),
+        };
+
+        self.connection
+            .execute(
+                "INSERT INTO jobs (input, magnet, torrent_file, link, name, state, out_dir, policy, updated)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![
+                    job.input.as_deref().map(path_text),
+                    magnet,
+                    torrent_file,
+                    link,
+                    job.name,
+                    value_name(job.state),
+                    path_text(&job.out_dir),
+                    value_name(job.policy),
+                    date::now(),
+                ],
+            )
+            .map_err(database_error)?;
+        job.id = self.connection.last_insert_rowid();
+
+        Ok(())
+    }
+
+    /// Saves the progress of a job.
+    fn save(&self, job: &mut Job) -> Result<(), ApiError> {
+        job.updated = date::now();
+        self.connection
+            .execute(
+                "UPDATE jobs SET torrent = ?2, name = ?3, state = ?4, attempts = ?5, error = ?6, updated = ?7
+                 WHERE id = ?1",
+                params![
+                    job.id,
+                    job.torrent,
+                    job.name,
+                    value_name(job.state),
+                    job.download_attempts,
+                    job.error,
+                    job.updated,
+                ],
+            )
+            .map(|_| ())
+            .map_err(database_error)
+    }
+
+    /// Records that a watched input file was moved.
+    pub(crate) fn move_input(&self, from: &Path, to: &Path) -> Result<(), ApiError> {
+        self.connection
+            .execute(
+                "UPDATE jobs SET input = ?2 WHERE input = ?1",
+                params![path_text(from), path_text(to)],
+            )
+            .map(|_| ())
+            .map_err(database_error)
+    }
+
+    /// Changes where a job downloads to.
+    pub(crate) fn set_out_dir(&self, id: i64, out_dir: &Path) -> Result<(), ApiError> {
+        self.connection
+            .execute(
+                "UPDATE jobs SET out_dir = ?2 WHERE id = ?1",
+                params![id, path_text(out_dir)],
+            )
+            .map(|_| ())
+            .map_err(database_error)
+    }
+
+    /// Forgets a job and its links.
+    pub(crate) fn delete(&self, id: i64) -> Result<(), ApiError> {
+        self.connection
+            .execute("DELETE FROM jobs WHERE id = ?1", [id])
+            .map(|_| ())
+            .map_err(database_error)
+    }
+
+    fn links(&self, job: i64) -> Result<Vec<Link>, ApiError> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT position, link, download, path, done FROM links WHERE job = ?1 ORDER BY position")
+            .map_err(database_error)?;
+
+        statement
+            .query_map([job], |row| {
+                Ok(Link {
+                    position: row.get("position")?,
+                    link: row.get("link")?,
+                    download: row.get("download")?,
+                    path: row.get::<_, Option<String>>("path")?.map(PathBuf::from),
+                    done: row.get("done")?,
+                })
+            })
+            .and_then(Iterator::collect)
+            .map_err(database_error)
+    }
+
+    fn save_link(&self, job: i64, link: &Link) -> Result<(), ApiError> {
+        self.connection
+            .execute(
+                "INSERT OR REPLACE INTO links (job, position, link, download, path, done)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    job,
+                    link.position,
+                    link.link,
+                    link.download,
+                    link.path.as_deref().map(path_text),
+                    link.done,
+                ],
+            )
+            .map(|_| ())
+            .map_err(database_error)
+    }
+
+    fn clear_links(&self, job: i64) -> Result<(), ApiError> {
+        self.connection
+            .execute("DELETE FROM links WHERE job = ?1", [job])
+            .map(|_| ())
+            .map_err(database_error)
+    }
+
+    /// Saves a job while it is advanced, where a failure must not stop the work.
+    fn record(&self, job: &mut Job) {
+        if let Err(e) = self.save(job) {
+            warn!("jobs : {} : {e}", job.id);
+        }
+    }
+}
+
+/// Adds the `ADDED_COLUMNS` missing from a database made with an older schema.
+fn add_columns(connection: &Connection) -> rusqlite::Result<()> {
+    let mut statement = connection.prepare("SELECT name FROM pragma_table_info('jobs')")?;
+    let columns = statement
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    for (column, definition) in ADDED_COLUMNS {
+        if !columns.iter().any(|name| name == column) {
+            connection.execute_batch(&format!(
+                "ALTER TABLE jobs ADD COLUMN {column} {definition}"
+            ))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn database_error(e: rusqlite::Error) -> ApiError {
+    ApiError::new(Failure::Api, format!("jobs database : {e}"))
+}
+
+/// `$XDG_DATA_HOME/traffic_cone`, or `~/.local/share/traffic_cone`.
+fn data_dir() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| Path::new(&home).join(".local").join("share"))
+        })?;
+
+    Some(base.join("traffic_cone"))
+}
+
+fn path_text(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+/// The command line name of a value, which is also its name in the database.
+fn value_name(value: impl ValueEnum) -> String {
+    value
+        .to_possible_value()
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_default()
+}
+
+fn parse_value<T: ValueEnum>(name: &str) -> Option<T> {
+    T::from_str(name, false).ok()
+}
+
+/// Submits a torrent and records it as a new job.
+///
+/// A submission refused by the API is recorded as a failed job,
+/// which `jobs retry` submits again. One which never reached the API,
+/// or was rate limited, stays `submitted` and is sent again when advanced.
+/// A hoster link is only recorded, as it has nothing to submit.
+pub(crate) fn submit(
+    store: &Store,
+    source: Source,
+    input: Option<&Path>,
+    out_dir: &Path,
+    policy: SelectPolicy,
+) -> Result<Job, ApiError> {
+    let name = input
+        .and_then(Path::file_stem)
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| source.name());
+
+    let mut job = Job {
+        id: 0,
+        input: input.map(Path::to_path_buf),
+        source,
+        torrent: None,
+        name,
+        state: JobState::Submitted,
+        out_dir: out_dir.to_path_buf(),
+        policy,
+        download_attempts: 0,
+        error: None,
+        updated: date::now(),
+    };
+    store.insert(&mut job)?;
+
+    if let Source::Link(_) = job.source {
+        return Ok(job);
+    }
+
+    match add(&job.source) {
+        Ok(torrent) => {
+            info!("jobs : {} : submitted as torrent {torrent}", job.id);
+            job.torrent = Some(torrent);
+            store.save(&mut job)?;
+            Ok(job)
+        }
+        Err(e) if matches!(e.failure(), Failure::Network | Failure::RateLimited) => {
+            warn!("jobs : {} : {e}, submitting again later", job.id);
+            job.error = Some(e.to_string());
+            store.save(&mut job)?;
+            Ok(job)
+        }
+        Err(e) => {
+            job.state = JobState::Failed;
+            job.error = Some(e.to_string());
+            store.save(&mut job)?;
+            Err(e)
+        }
+    }
+}
+
+/// Adds the torrent of a job to Real-Debrid, returning its id.
+fn add(source: &Source) -> Result<Id, ApiError> {
+    let body = match source {
+        Source::Magnet(magnet) => torrents::add_magnet(magnet.clone())?,
+        Source::TorrentFile(torrent_file) => torrents::add_torrent(torrent_file.clone(), None)?,
+        Source::Link(link) => {
+            return Err(ApiError::new(
+                Failure::InvalidInput,
+                format!("jobs : `{link}` is not a torrent"),
+            ));
+        }
+    };
+
+    serde_json::from_str::<Value>(&body)
+        .ok()
+        .and_then(|added| added["id"].as_str().map(String::from))
+        .ok_or_else(|| ApiError::new(Failure::Api, format!("no torrent id in `{body}`")))
+}
+
+/// Advances a job according to the status of its torrent,
+/// or downloads its hoster link.
+pub(crate) fn advance(store: &Store, job: &mut Job) -> Progress {
+    if let Source::Link(link) = &job.source {
+        let links = [link.clone()];
+        let dir = job.out_dir.clone();
+        return fetch(store, job, &links, &dir);
+    }
+
+    let Some(torrent) = job.torrent.clone() else {
+        return match add(&job.source) {
+            Ok(torrent) => {
+                info!("jobs : {} : submitted as torrent {torrent}", job.id);
+                job.torrent = Some(torrent);
+                job.error = None;
+                store.record(job);
+                Progress::Pending
+            }
+            Err(e) if matches!(e.failure(), Failure::Network | Failure::RateLimited) => {
+                warn!("jobs : {} : {e}", job.id);
+                Progress::Pending
+            }
+            Err(e) => fail(store, job, e.to_string()),
+        };
+    };
+
+    let info = match torrents::get_torrent_info(torrent.clone()) {
+        Ok(info) => serde_json::from_str::<Value>(&info).unwrap_or_default(),
+        Err(e) if *e.failure() == Failure::NotFound => return fail(store, job, e.to_string()),
+        Err(e) => {
+            warn!("jobs : {} : {e}", job.id);
+            return Progress::Pending;
+        }
+    };
+
+    if let Some(name) = info["filename"].as_str() {
+        job.name = name.to_string();
+    }
+
+    match info["status"].as_str().unwrap_or_default() {
+        "waiting_files_selection" => {
+            let files = select_files(&info, job.policy);
+            debug!("jobs : {} : selecting files {files}", job.id);
+            if let Err(e) = torrents::select_files(torrent, files) {
+                warn!("jobs : {} : {e}", job.id);
+            }
+            Progress::Pending
+        }
+        "downloaded" => {
+            let links = info["links"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|link| link.as_str().unwrap_or_default().to_string())
+                .collect::<Vec<_>>();
+            let dir = job.out_dir.join(sanitize_filename(&job.name));
+            fetch(store, job, &links, &dir)
+        }
+        status if FAILED_STATUSES.contains(&status) => {
+            fail(store, job, format!("torrent status `{status}`"))
+        }
+        status => {
+            debug!(
+                "jobs : {} : {status} {}%",
+                job.id,
+                info["progress"].as_f64().unwrap_or(0.0)
+            );
+            store.record(job);
+            Progress::Pending
+        }
+    }
+}
+
+/// Downloads `hoster_links` into `dir`, giving up after `MAX_DOWNLOAD_ATTEMPTS`.
+fn fetch(store: &Store, job: &mut Job, hoster_links: &[String], dir: &Path) -> Progress {
+    job.state = JobState::Downloading;
+    store.record(job);
+
+    match download(store, job, hoster_links, dir) {
+        Ok(()) => {
+            info!("jobs : {} : `{}` downloaded", job.id, job.name);
+            job.state = JobState::Done;
+            job.error = None;
+            store.record(job);
+            Progress::Done
+        }
+        Err(e) => {
+            job.download_attempts += 1;
+            warn!(
+                "jobs : {} : download attempt {} of {MAX_DOWNLOAD_ATTEMPTS} : {e}",
+                job.id, job.download_attempts
+            );
+            if job.download_attempts < MAX_DOWNLOAD_ATTEMPTS {
+                store.record(job);
+                Progress::Pending
+            } else {
+                fail(store, job, e.to_string())
+            }
+        }
+    }
+}
+
+fn fail(store: &Store, job: &mut Job, reason: String) -> Progress {
+    job.state = JobState::Failed;
+    job.error = Some(reason.clone());
+    store.record(job);
+
+    Progress::Failed(reason)
+}
+
+/// The `selectFiles` argument for a torrent, according to `policy`.
+pub(crate) fn select_files(info: &Value, policy: SelectPolicy) -> String {
+    let files = info["files"].as_array().cloned().unwrap_or_default();
+    let id = |file: &Value| file["id"].to_string();
+    let largest = || {
+        files
+            .iter()
+            .max_by_key(|file| file["bytes"].as_u64().unwrap_or(0))
+            .map(id)
+            .unwrap_or_else(|| String::from("all"))
+    };
+
+    match policy {
+        SelectPolicy::All => String::from("all"),
+        SelectPolicy::Largest => largest(),
+        SelectPolicy::Video => {
+            let videos = files
+                .iter()
+                .filter(|file| {
+                    let path = file["path"].as_str().unwrap_or_default().to_lowercase();
+                    VIDEO_EXTENSIONS
+                        .iter()
+                        .any(|extension| path.ends_with(&format!(".{extension}")))
+                })
+                .map(id)
+                .collect::<Vec<_>>();
+
+            if videos.is_empty() {
+                largest()
+            } else {
+                videos.join(",")
+            }
+        }
+    }
+}
+
+/// Unrestricts and downloads every link of a job into `dir`,
+/// `<out-dir>/<torrent name>/` for a torrent.
+///
+/// Links already downloaded by an earlier attempt are skipped.
+fn download(store: &Store, job: &Job, hoster_links: &[String], dir: &Path) -> Result<(), ApiError> {
+    let mut links = store.links(job.id)?;
+    if links.is_empty() {
+        links = hoster_links
+            .iter()
+            .enumerate()
+            .map(|(position, link)| Link {
+                position,
+                link: link.clone(),
+                download: None,
+                path: None,
+                done: false,
+            })
+            .collect();
+    }
+
+    quota::preflight(
+        links
+            .iter()
+            .filter(|link| !link.done && link.download.is_none())
+            .map(|link| link.link.as_str()),
+    );
+
+    for link in links.iter_mut().filter(|link| !link.done) {
+        let (url, path) = match (&link.download, &link.path) {
+            (Some(url), Some(path)) => (url.clone(), path.clone()),
+            _ => {
+                let unrestricted =
+                    serde_json::from_str::<Value>(&unrestrict::link(link.link.clone())?)
+                        .unwrap_or_default();
+
+                let (Some(url), Some(filename)) = (
+                    unrestricted["download"].as_str(),
+                    unrestricted["filename"].as_str(),
+                ) else {
+                    return Err(ApiError::new(Failure::Api, "unrestrict : no download link"));
+                };
+
+                link.download = Some(url.to_string());
+                link.path = Some(dir.join(sanitize_filename(filename)));
+                store.save_link(job.id, link)?;
+                (url.to_string(), dir.join(sanitize_filename(filename)))
+            }
+        };
+
+        if !path.exists()
+            && let Err(e) = download_to(&url, &path)
+        {
+            // The unrestricted link may have expired, so unrestrict again next time.
+            link.download = None;
+            store.save_link(job.id, link)?;
+            return Err(e);
+        }
+
+        link.done = true;
+        store.save_link(job.id, link)?;
+    }
+
+    Ok(())
+}
+
+/// Queues a magnet link or `.torrent` file as a new job.
+pub fn add_job(store: &Store, source: &str, out_dir: &Path, policy: SelectPolicy) -> ApiResult {
+    let job = submit(store, Source::read(source)?, None, out_dir, policy)?;
+
+    summary(store, &[job])
+}
+
+/// Lists the recorded jobs.
+pub fn list(store: &Store, state: Option<JobState>) -> ApiResult {
+    summary(store, &store.list(state)?)
+}
+
+/// Advances unfinished jobs, or only job `id`, until they are done or failed.
+pub fn resume(store: &Store, id: Option<i64>, interval: Duration) -> ApiResult {
+    let mut jobs = match id {
+        Some(id) => vec![store.get(id)?],
+        None => store.unfinished()?,
+    };
+    jobs.retain(|job| !job.is_finished());
+
+    let ids = jobs.iter().map(|job| job.id).collect::<Vec<_>>();
+    let mut failures = 0;
+
+    if jobs.is_empty() {
+        info!("jobs : nothing to resume");
+    }
+
+    while !jobs.is_empty() {
+        jobs.retain_mut(|job| match advance(store, job) {
+            Progress::Pending => true,
+            Progress::Done => false,
+            Progress::Failed(reason) => {
+                error!("jobs : {} : {reason}", job.id);
+                failures += 1;
+                false
+            }
+        });
+
+        if !jobs.is_empty() {
+            thread::sleep(interval);
+        }
+    }
+
+    if failures > 0 {
+        return Err(ApiError::new(
+            Failure::Api,
+            format!("{failures} of {} jobs failed", ids.len()),
+        ));
+    }
+
+    let jobs = ids
+        .into_iter()
+        .map(|id| store.get(id))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    summary(store, &jobs)
+}
+
+/// Resets a failed job and advances it until it is done or failed again.
+///
+/// A torrent which failed on Real-Debrid, or was deleted there,
+/// is submitted again from the recorded source.
+pub fn retry(store: &Store, id: i64, interval: Duration) -> ApiResult {
+    let mut job = store.get(id)?;
+    if job.state != JobState::Failed {
+        return Err(ApiError::new(
+            Failure::InvalidInput,
+            format!("jobs : job {id} has not failed"),
+        ));
+    }
+
+    if let Some(torrent) = job.torrent.clone() {
+        let resubmit = match torrents::get_torrent_info(torrent.clone()) {
+            Ok(info) => {
+                let info = serde_json::from_str::<Value>(&info).unwrap_or_default();
+                let status = info["status"].as_str().unwrap_or_default();
+                let failed = FAILED_STATUSES.contains(&status);
+                if failed && let Err(e) = torrents::delete(torrent) {
+                    warn!("jobs : {id} : {e}");
+                }
+                failed
+            }
+            Err(e) if *e.failure() == Failure::NotFound => true,
+            Err(e) => return Err(e),
+        };
+
+        if resubmit {
+            job.torrent = None;
+            store.clear_links(id)?;
+        }
+    }
+
+    job.state = JobState::Submitted;
+    job.download_attempts = 0;
+    job.error = None;
+    store.save(&mut job)?;
+
+    resume(store, Some(id), interval)
+}
+
+/// How many links of a job are downloaded, out of how many,
+/// and how many bytes are on disk, including incomplete files.
+pub(crate) fn local_progress(store: &Store, job: &Job) -> Result<(usize, usize, u64), ApiError> {
+    let links = store.links(job.id)?;
+    let done = links.iter().filter(|link| link.done).count();
+    let bytes = links
+        .iter()
+        .filter_map(|link| link.path.as_deref())
+        .filter_map(|path| {
+            fs::metadata(path)
+                .or_else(|_| fs::metadata(part_path(path)))
+                .ok()
+        })
+        .map(|metadata| metadata.len())
+        .sum();
+
+    Ok((done, links.len(), bytes))
+}
+
+/// Lists jobs as `id  state  files  size  name`, or a json array with `--output json`.
+fn summary(store: &Store, jobs: &[Job]) -> ApiResult {
+    let mut lines = Vec::new();
+    let mut objects = Vec::new();
+
+    for job in jobs {
+        let (done, total, bytes) = local_progress(store, job)?;
+
+        match ARGS.output() {
+            Output::Text => {
+                let mut line = format!(
+                    "{}\t{:<11}\t{done}/{total} files\t{:>10}\t{}",
+                    job.id,
+                    value_name(job.state),
+                    humanize(bytes),
+                    job.name
+                );
+                if let Some(error) = &job.error {
+                    line.push_str(&format!("\t{error}"));
+                }
+                lines.push(line);
+            }
+            Output::Json => objects.push(json!({
+                "id": job.id,
+                "state": value_name(job.state),
+                "torrent": job.torrent,
+                "name": job.name,
+                "input": job.input.as_deref().map(path_text),
+                "out_dir": path_text(&job.out_dir),
+                "select": value_name(job.policy),
+                "download_attempts": job.download_attempts,
+                "error": job.error,
+                "files_done": done,
+                "files": total,
+                "bytes": bytes,
+                "updated": date::rfc3339(UNIX_EPOCH + Duration::from_secs(job.updated.max(0) as u64)),
+            })),
+        }
+    }
+
+    Ok(match ARGS.output() {
+        Output::Text => lines.join("\n"),
+        Output::Json => Value::from(objects).to_string(),
+    })
+}
@@ -1,25 +1,50 @@
 #![feature(never_type)]
-#![feature(stmt_expr_attributes)]
 
 
 //! # traffic_cone API caller
 
 use std::collections::HashMap;
+use std::time::Instant;
 
+use crate::error::{ApiError, Failure};
 use crate::prelude::*;
-use reqwest::blocking::{
-    Client as ReqwestClient, RequestBuilder as ReqwestBuilder, Response as ReqwestResponse,
-};
+use reqwest::StatusCode;
+use reqwest::blocking::{Client as ReqwestClient, RequestBuilder as ReqwestBuilder};
 
 pub use crate::app::ARGS;
 
 pub(crate) type Json = String;
 pub(crate) type Url = String;
+pub(crate) type ApiResult = Result<Json, ApiError>;
 
 type Body = String;
 
+#[macro_use]
+pub mod log;
+
+pub mod account;
 pub mod app;
+pub mod batch;
+pub mod bulk;
+pub mod complete;
+pub mod configure;
+pub mod daemon;
+pub mod date;
+pub mod error;
+pub mod export;
+pub mod fetch;
 pub mod handle;
+pub mod hoster;
+pub mod jobs;
+pub mod library;
+pub mod media;
+#[cfg(all(feature = "fuse", target_os = "linux"))]
+pub mod mount;
+pub mod play;
+pub mod quota;
+pub mod serve;
+pub mod size;
+pub mod usage;
 
 pub mod downloads;
 pub mod hosts;
@@ -29,84 +54,52 @@ pub mod torrents;
 pub mod traffic;
 pub mod unrestrict;
 pub mod user;
+
+mod pick;
+mod trace;
+mod tui;
+
 pub(crate) mod prelude {
-    pub(crate) use crate::{HttpRequest::*, Json, send};
+    pub(crate) use crate::{ApiResult, HttpRequest::*, get_all_pages, send, send_file};
     pub(crate) use std::{fs::File, io::Read, process::exit, sync::LazyLock};
 }
 
-#[macro_export]
-macro_rules! debug {
-    ($($tt:tt)*) => {
-        #[cfg(debug_assertions)]
-        if !*crate::ARGS.quiet() {
-            let level = "DEBUG";
-            if *crate::ARGS.no_color() {
-                eprintln!("{level}:   {}", format!($($tt)*))
-            } else {
-                eprintln!("\x1b[38;5;12m{level}\x1b[0m:   {}", format!($($tt)*));
-            }
-        }
-    };
-}
+/// Memoization of the API Key
+static API_KEY: LazyLock<String> = LazyLock::new(|| {
+    if complete::is_completing() {
+        return complete::api_key().unwrap_or_default();
+    }
 
-#[macro_export]
-macro_rules! warn {
-    ($($tt:tt)*) => {
-        if !*crate::ARGS.quiet() {
-            let level = "WARNING";
-            if *crate::ARGS.no_color() {
-                eprintln!("{level}: {}", format!($($tt)*))
-            } else {
-                eprintln!("\x1b[38;5;11m{level}\x1b[0m: {}", format!($($tt)*))
-            }
-        }
+    let Some(api_key_path) = ARGS.api_key_path() else {
+        error!("api key : no API KEY given, see `--key-path`");
+        exit(Failure::Auth.exit_code())
     };
-}
 
-#[macro_export]
-macro_rules! error {
-    ($($tt:tt)*) => {
-        if !*crate::ARGS.quiet() {
-            let level = "ERROR";
-            if *crate::ARGS.no_color() {
-                eprintln!("{level}:   {}", format!($($tt)*))
-            } else {
-                eprintln!("\x1b[38;5;9m{level}\x1b[0m:   {}", format!($($tt)*))
-            }
-        }
-    };
-}
-
-/// Memoization of the API Key
-static API_KEY: LazyLock<String> = LazyLock::new(|| {
-    let mut file = File::open(ARGS.api_key_path())
+    read_api_key(api_key_path)
         .inspect_err(|e| {
-            error!(
-                "api key : could not locate API KEY `{}` : {e}",
-                ARGS.api_key_path()
-            );
-            exit(1)
+            error!("api key : could not read API KEY `{api_key_path}` : {e}");
+            exit(Failure::Auth.exit_code())
         })
-        .unwrap();
+        .unwrap()
+});
 
+/// Reads the API key from the first line of the file at `path`.
+pub(crate) fn read_api_key(path: &str) -> std::io::Result<String> {
     let mut api_key = String::new();
+    File::open(path)?.read_to_string(&mut api_key)?;
 
-    file.read_to_string(&mut api_key)
-        .inspect_err(|e| {
-            error!(
-                "api key : failed while reading contents of API KEY `{}` : {e}",
-                ARGS.api_key_path()
-            );
-            exit(1)
-        })
-        .unwrap();
+    Ok(api_key.lines().next().unwrap_or_default().to_string())
+}
 
-    api_key.lines().next().unwrap_or_default().to_string()
+/// Memoization of the reqwest `Client`, which gives up early while completing.
+static HTTP_CLIENT: LazyLock<ReqwestClient> = LazyLock::new(|| match complete::is_completing() {
+    true => ReqwestClient::builder()
+        .timeout(complete::TIMEOUT)
+        .build()
+        .unwrap_or_default(),
+    false => ReqwestClient::new(),
 });
 
-/// Memoization of the reqwest `Client`.
-static HTTP_CLIENT: LazyLock<ReqwestClient> = LazyLock::new(|| ReqwestClient::new());
-
 #[allow(dead_code)]
 pub(crate) enum HttpRequest<T: Into<Body>> {
     Get(T),
@@ -124,48 +117,123 @@ where
         }
     }
 
-    pub(crate) fn send_to<'a>(self, url: impl Into<Url>) -> Option<ReqwestResponse> {
-        let report_err = |response: Result<ReqwestResponse, reqwest::Error>| -> Result<ReqwestResponse, reqwest::Error> {
-            return response
-                .inspect_err(|e| error!("http_response : {e}"));
-        };
-
-        let debug_response = |response: Result<ReqwestResponse, reqwest::Error>| -> Result<ReqwestResponse, reqwest::Error> {
-            #[allow(unused_variables)]
-            response.inspect(|response| {debug!("STATUS CODE: {}", response.status()); ()})
-        };
-
+    /// Sends the request and reads the whole response.
+    pub(crate) fn send_to(self, url: impl Into<Url>) -> Result<(StatusCode, Json), ApiError> {
         let body = self.body();
-        let request = default_headers(match self {
+        execute(default_headers(match self {
             Get(_) => HTTP_CLIENT.get(url.into()).body(self.body()),
-            Post(_) => HTTP_CLIENT.post(url.into()).form(
-                &serde_json::de::from_str::<HashMap<&str, &str>>(&body)
-                    .inspect_err(|e| error!("derserialization : {e}"))
-                    .unwrap_or_default(),
-            ),
+            Post(_) => HTTP_CLIENT.post(url.into()).form(&form_fields(&body)),
             Delete(_) => HTTP_CLIENT.delete(url.into()).body(self.body()),
             Put(_) => HTTP_CLIENT.put(url.into()).body(self.body()),
-        });
-
-        debug!("{request:?}");
-
-        let response = request.send();
-
-        debug_response(report_err(response)).ok()
+        }))
     }
 }
 
-fn send<B: Into<Body> + Clone, Link: Into<Url>>(request: HttpRequest<B>, to: Link) -> String {
+/// Reads the form fields of a request body, given as a json object of strings.
+///
+/// Fields are owned, as escaped characters like `"` or `\` cannot be borrowed.
+fn form_fields(body: &str) -> HashMap<String, String> {
+    serde_json::from_str(body)
+        .inspect_err(|e| error!("derserialization : {e}"))
+        .unwrap_or_default()
+}
+
+/// Sends a built request, logging and tracing the exchange, and reads the whole response.
+fn execute(request: ReqwestBuilder) -> Result<(StatusCode, Json), ApiError> {
     let report_read_error = |response: std::io::Result<usize>| -> usize {
         response.inspect_err(|e| warn!("io read: {e}")).unwrap_or(0)
     };
 
+    let request = request
+        .build()
+        .map_err(|e| ApiError::new(Failure::InvalidInput, e.to_string()))?;
+
+    let method = request.method().clone();
+    let url = request.url().clone();
+    debug!(
+        "{method} {url} : headers {}",
+        crate::log::redacted_headers(request.headers())
+    );
+
+    let exchange = trace::Exchange::start(&request);
+
+    let started = Instant::now();
+    let mut response = match HTTP_CLIENT.execute(request) {
+        Ok(response) => response,
+        Err(e) => {
+            let elapsed = started.elapsed();
+            info!("{method} {url} : failed in {}ms : {e}", elapsed.as_millis());
+            if let Some(exchange) = exchange {
+                exchange.fail(&e, elapsed);
+            }
+
+            return Err(ApiError::from(e));
+        }
+    };
+
+    let status = response.status();
     let mut response_json = String::new();
-    if let Some(mut response) = request.send_to(to) {
-        report_read_error(response.read_to_string(&mut response_json));
+    report_read_error(response.read_to_string(&mut response_json));
+
+    let elapsed = started.elapsed();
+    info!("{method} {url} : {status} in {}ms", elapsed.as_millis());
+    if let Some(exchange) = exchange {
+        exchange.finish(status, response.headers(), &response_json, elapsed);
+    }
+
+    Ok((status, response_json))
+}
+
+/// Classifies the status of a read response.
+fn classify((status, response_json): (StatusCode, Json)) -> ApiResult {
+    if status.is_success() {
+        Ok(response_json)
+    } else {
+        Err(ApiError::from_response(status.as_u16(), response_json))
+    }
+}
+
+/// Sends the request and reads the response body.
+///
+/// Any non-success status is classified into an `ApiError`.
+fn send<B: Into<Body> + Clone, Link: Into<Url>>(request: HttpRequest<B>, to: Link) -> ApiResult {
+    classify(request.send_to(to)?)
+}
+
+/// Uploads a file as the raw body of a `PUT` request.
+///
+/// Any non-success status is classified into an `ApiError`.
+fn send_file(file: Vec<u8>, mime_type: &str, to: impl Into<Url>) -> ApiResult {
+    let request = HTTP_CLIENT
+        .put(to.into())
+        .header("Authorization", format!("Bearer {}", API_KEY.as_str()))
+        .header("Content-Type", mime_type)
+        .body(file);
+
+    classify(execute(request)?)
+}
+
+/// The most entries the API returns per page.
+const PAGE_LIMIT: usize = 5000;
+
+/// Requests every page of a listing and joins them into one json array.
+fn get_all_pages(url: &str) -> ApiResult {
+    let mut entries = Vec::new();
+
+    for page in 1.. {
+        let body = send(Get(""), format!("{url}?page={page}&limit={PAGE_LIMIT}"))?;
+        let Ok(serde_json::Value::Array(page)) = serde_json::from_str(&body) else {
+            break;
+        };
+
+        let count = page.len();
+        entries.extend(page);
+        if count < PAGE_LIMIT {
+            break;
+        }
     }
 
-    response_json
+    Ok(serde_json::Value::Array(entries).to_string())
 }
 
 /// Extends the request with default header information.
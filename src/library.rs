@@ -0,0 +1,257 @@
+//! # Library Module
+//!
+//! This module presents the downloaded torrents as a file tree,
+//! shared by the WebDAV server and the FUSE mount.
+//!
+//! Every downloaded torrent is a folder holding the selected files
+//! of the torrent, flattened to their file names, with the file id
+//! added to names taken already.
+//! A torrent whose links do not pair up with its selected files,
+//! like one packed into an archive, is left empty with a warning.
+//!
+//! Listings are reused for `LISTING_TTL`, and the files of a torrent
+//! for as long as the library is served, as they do not change once downloaded.
+//!
+//! The link of a file is unrestricted on demand, and the unrestricted
+//! link is cached until it expires.
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+
+use crate::date;
+use crate::error::{ApiError, Failure};
+use crate::fetch::sanitize_filename;
+use crate::{torrents, unrestrict};
+
+/// How long listings are reused for.
+pub(crate) const LISTING_TTL: Duration = Duration::from_secs(30);
+
+/// A downloaded torrent.
+#[derive(Clone)]
+pub(crate) struct Folder {
+    pub(crate) id: String,
+    /// The file name of the torrent, unique among folders.
+    pub(crate) name: String,
+    pub(crate) bytes: u64,
+    /// Seconds since the unix epoch.
+    pub(crate) added: i64,
+}
+
+/// A file of a downloaded torrent.
+#[derive(Clone)]
+pub(crate) struct File {
+    pub(crate) name: String,
+    pub(crate) bytes: u64,
+    /// The hoster link to unrestrict.
+    pub(crate) link: String,
+}
+
+/// The downloaded torrents, with unique folder names.
+pub(crate) fn folders() -> Result<Vec<Folder>, ApiError> {
+    let torrents =
+        serde_json::from_str::<Value>(&torrents::get_all_torrents()?).unwrap_or_default();
+
+    let mut names = HashSet::new();
+    let folders = torrents
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|torrent| torrent["status"] == "downloaded")
+        .filter_map(|torrent| {
+            let id = torrent["id"].as_str()?.to_string();
+            let mut name = sanitize_filename(torrent["filename"].as_str().unwrap_or(&id));
+            if !names.insert(name.clone()) {
+                name = format!("{name} ({id})");
+            }
+
+            Some(Folder {
+                name,
+                bytes: torrent["bytes"].as_u64().unwrap_or(0),
+                added: torrent["added"]
+                    .as_str()
+                    .and_then(date::parse_rfc3339)
+                    .unwrap_or(0),
+                id,
+            })
+        })
+        .collect();
+
+    Ok(folders)
+}
+
+/// The selected files of a torrent, paired with its links.
+pub(crate) fn files(torrent: &str) -> Result<Vec<File>, ApiError> {
+    let info = serde_json::from_str::<Value>(&torrents::get_torrent_info(torrent.to_string())?)
+        .unwrap_or_default();
+
+    let links = info["links"].as_array().cloned().unwrap_or_default();
+    let selected = info["files"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|file| file["selected"] == 1)
+        .collect::<Vec<_>>();
+
+    if selected.len() != links.len() {
+        warn!(
+            "library : torrent {torrent} has {} selected files but {} links, its files are left out",
+            selected.len(),
+            links.len()
+        );
+        return Ok(Vec::new());
+    }
+
+    let mut names = HashSet::new();
+    let files = selected
+        .into_iter()
+        .zip(links)
+        .map(|(file, link)| {
+            let path = file["path"].as_str().unwrap_or_default();
+            let mut name = sanitize_filename(path.rsplit('/').next().unwrap_or(path));
+            if !names.insert(name.clone()) {
+                name = with_id(&name, &file["id"].to_string());
+            }
+
+            File {
+                name,
+                bytes: file["bytes"].as_u64().unwrap_or(0),
+                link: link.as_str().unwrap_or_default().to_string(),
+            }
+        })
+        .collect();
+
+    Ok(files)
+}
+
+/// Adds ` (<id>)` to a file name, before its extension.
+fn with_id(name: &str, id: &str) -> String {
+    let path = Path::new(name);
+    match (path.file_stem(), path.extension()) {
+        (Some(stem), Some(extension)) => format!(
+            "{} ({id}).{}",
+            stem.to_string_lossy(),
+            extension.to_string_lossy()
+        ),
+        _ => format!("{name} ({id})"),
+    }
+}
+
+/// The downloaded torrents and their files, reused between requests.
+#[derive(Default)]
+pub(crate) struct Cache {
+    listing: Mutex<Option<(Instant, Vec<Folder>)>>,
+    /// The files of each torrent, which do not change once it is downloaded.
+    files: Mutex<HashMap<String, Vec<File>>>,
+}
+impl Cache {
+    /// The downloaded torrents, reused for `LISTING_TTL`.
+    pub(crate) fn folders(&self) -> Result<Vec<Folder>, ApiError> {
+        let mut listing = self.listing.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((at, folders)) = listing.as_ref()
+            && at.elapsed() < LISTING_TTL
+        {
+            return Ok(folders.clone());
+        }
+
+        let folders = folders()?;
+        *listing = Some((Instant::now(), folders.clone()));
+
+        Ok(folders)
+    }
+
+    /// The files of a torrent, fetched once.
+    pub(crate) fn files(&self, torrent: &str) -> Result<Vec<File>, ApiError> {
+        if let Some(files) = self
+            .files
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(torrent)
+        {
+            return Ok(files.clone());
+        }
+
+        let files = files(torrent)?;
+        self.files
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(torrent.to_string(), files.clone());
+
+        Ok(files)
+    }
+}
+
+/// Unrestricted links by hoster link.
+pub(crate) struct Links {
+    ttl: Duration,
+    cache: Mutex<HashMap<String, (Instant, String)>>,
+}
+impl Links {
+    /// Caches unrestricted links for at most `ttl`.
+    pub(crate) fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The unrestricted link of a file, unrestricting it unless cached.
+    pub(crate) fn download_url(&self, file: &File) -> Result<String, ApiError> {
+        if let Some((at, url)) = self
+            .cache
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&file.link)
+            && at.elapsed() < self.ttl
+        {
+            return Ok(url.clone());
+        }
+
+        let unrestricted = serde_json::from_str::<Value>(&unrestrict::link(file.link.clone())?)
+            .unwrap_or_default();
+        let url = unrestricted["download"]
+            .as_str()
+            .ok_or_else(|| ApiError::new(Failure::Api, "unrestrict : no download link"))?
+            .to_string();
+
+        debug!("library : `{}` unrestricted", file.name);
+        self.cache
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(file.link.clone(), (Instant::now(), url.clone()));
+
+        Ok(url)
+    }
+
+    /// Remembers an unrestricted link of a file, unless one is already cached.
+    #[cfg(all(feature = "fuse", target_os = "linux"))]
+    pub(crate) fn remember(&self, file: &File, url: &str) {
+        self.cache
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(file.link.clone())
+            .or_insert_with(|| (Instant::now(), url.to_string()));
+    }
+
+    /// Forgets the unrestricted link of a file, once it stopped working.
+    pub(crate) fn expire(&self, file: &File) {
+        self.cache
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&file.link);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_go_before_the_extension() {
+        assert_eq!(with_id("episode.mkv", "3"), "episode (3).mkv");
+        assert_eq!(with_id("README", "12"), "README (12)");
+    }
+}
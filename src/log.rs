@@ -0,0 +1,205 @@
+//! # Log Module
+//!
+//! This module provides the leveled logger behind the
+//! `error!`, `warn!`, `info!`, `debug!` and `trace!` macros.
+//!
+//! ## Verbosity
+//!
+//! Errors and warnings are always emitted, unless `--quiet` is given.
+//! Each `-v` enables one more level: `info`, `debug`, then `trace`.
+//! `--trace-http` implies `-vvv`.
+//!
+//! ## Destination
+//!
+//! Records are written to stderr, or appended to `--log-file` when given.
+//! Color is only used for `text` records written to a terminal,
+//! and never when `--no-color` is given or `NO_COLOR` is set.
+
+use std::fmt::{self, Display};
+use std::fs::{File, OpenOptions};
+use std::io::{IsTerminal, Write};
+use std::sync::{LazyLock, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use reqwest::header::{AUTHORIZATION, HeaderMap};
+use serde_json::json;
+
+use crate::ARGS;
+use crate::app::LogFormat;
+
+/// The severity of a log record.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+impl Level {
+    /// The ANSI 256-color code of this level.
+    fn color(self) -> u8 {
+        use Level::*;
+
+        match self {
+            Error => 9,
+            Warn => 11,
+            Info => 10,
+            Debug => 12,
+            Trace => 8,
+        }
+    }
+}
+impl Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Level::*;
+
+        let name = match self {
+            Error => "ERROR",
+            Warn => "WARNING",
+            Info => "INFO",
+            Debug => "DEBUG",
+            Trace => "TRACE",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+/// The most verbose level that is emitted, or `None` when quiet.
+static MAX_LEVEL: LazyLock<Option<Level>> = LazyLock::new(|| {
+    use Level::*;
+
+    // Completion output is read by the shell.
+    if crate::complete::is_completing() || *ARGS.quiet() {
+        return None;
+    }
+
+    if *ARGS.trace_http() {
+        return Some(Trace);
+    }
+
+    Some(match ARGS.verbose() {
+        0 => Warn,
+        1 => Info,
+        2 => Debug,
+        _ => Trace,
+    })
+});
+
+/// Memoization of the opened `--log-file`.
+static LOG_FILE: LazyLock<Option<Mutex<File>>> = LazyLock::new(|| {
+    let path = ARGS.log_file().as_ref()?;
+
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .inspect_err(|e| eprintln!("ERROR:   log file : could not open `{path}` : {e}"))
+        .ok()
+        .map(Mutex::new)
+});
+
+/// Whether records are colored.
+static COLOR: LazyLock<bool> = LazyLock::new(|| {
+    let no_color_env = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+
+    !*ARGS.no_color()
+        && !no_color_env
+        && *ARGS.log_format() == LogFormat::Text
+        && ARGS.log_file().is_none()
+        && std::io::stderr().is_terminal()
+});
+
+/// Whether records of this level are emitted.
+pub fn enabled(level: Level) -> bool {
+    MAX_LEVEL.is_some_and(|max| level <= max)
+}
+
+/// Emits a single log record.
+///
+/// Prefer the logging macros over calling this directly.
+pub fn log(level: Level, message: fmt::Arguments) {
+    if !enabled(level) {
+        return;
+    }
+
+    let record = match ARGS.log_format() {
+        LogFormat::Text if *COLOR => {
+            let label = format!("\x1b[38;5;{}m{level}\x1b[0m:", level.color());
+            // Pad as if the escape codes were not there.
+            let width = 9 + label.len() - format!("{level}:").len();
+            format!("{label:<width$}{message}")
+        }
+        LogFormat::Text => format!("{:<9}{message}", format!("{level}:")),
+        LogFormat::Json => json!({
+            "timestamp": SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs_f64(),
+            "level": level.to_string().to_lowercase(),
+            "message": message.to_string(),
+        })
+        .to_string(),
+    };
+
+    match LOG_FILE.as_ref() {
+        Some(file) => {
+            let mut file = file.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            let _ = writeln!(file, "{record}");
+        }
+        None => eprintln!("{record}"),
+    }
+}
+
+/// Formats headers for logging, with credentials redacted.
+pub(crate) fn redacted_headers(headers: &HeaderMap) -> String {
+    let headers = headers
+        .iter()
+        .map(|(name, value)| {
+            let value = if name == AUTHORIZATION {
+                "<redacted>"
+            } else {
+                value.to_str().unwrap_or("<binary>")
+            };
+            format!("{name}: {value}")
+        })
+        .collect::<Vec<_>>();
+
+    format!("{{{}}}", headers.join(", "))
+}
+
+#[macro_export]
+macro_rules! error {
+    ($($tt:tt)*) => {
+        $crate::log::log($crate::log::Level::Error, format_args!($($tt)*))
+    };
+}
+
+#[macro_export]
+macro_rules! warn {
+    ($($tt:tt)*) => {
+        $crate::log::log($crate::log::Level::Warn, format_args!($($tt)*))
+    };
+}
+
+#[macro_export]
+macro_rules! info {
+    ($($tt:tt)*) => {
+        $crate::log::log($crate::log::Level::Info, format_args!($($tt)*))
+    };
+}
+
+#[macro_export]
+macro_rules! debug {
+    ($($tt:tt)*) => {
+        $crate::log::log($crate::log::Level::Debug, format_args!($($tt)*))
+    };
+}
+
+#[macro_export]
+macro_rules! trace {
+    ($($tt:tt)*) => {
+        $crate::log::log($crate::log::Level::Trace, format_args!($($tt)*))
+    };
+}
@@ -2,5 +2,7 @@ use lib::app::ARGS;
 use lib::handle::handle_mode;
 
 fn main() -> ! {
+    lib::complete::complete();
+
     handle_mode(ARGS.mode().clone())
 }
@@ -0,0 +1,377 @@
+//! # Media Module
+//!
+//! This module reads the media informations of a download
+//! into a `MediaInfos`, rendered as a readable summary.
+//!
+//! It also finds the downloads with a given audio language or resolution,
+//! which costs one media informations request per streamable download.
+
+use std::path::Path;
+
+use serde_json::{Value, json};
+
+use crate::app::Output;
+use crate::error::{ApiError, Failure};
+use crate::prelude::*;
+use crate::size::humanize;
+use crate::{ARGS, downloads, streaming};
+
+/// Standard resolutions as `(width, height)`, named after their height.
+const RESOLUTIONS: [(u64, u64); 7] = [
+    (3840, 2160),
+    (2560, 1440),
+    (1920, 1080),
+    (1280, 720),
+    (1024, 576),
+    (854, 480),
+    (640, 360),
+];
+
+/// The media informations of a download.
+#[derive(Clone, Debug, Default)]
+pub struct MediaInfos {
+    pub filename: String,
+    /// In seconds.
+    pub duration: f64,
+    /// In bits per second.
+    pub bitrate: u64,
+    pub size: u64,
+    pub video: Vec<VideoTrack>,
+    pub audio: Vec<AudioTrack>,
+    pub subtitles: Vec<SubtitleTrack>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct VideoTrack {
+    pub codec: String,
+    pub width: u64,
+    pub height: u64,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct AudioTrack {
+    pub language: Language,
+    pub codec: String,
+    /// Like `5.1`.
+    pub channels: f64,
+    /// In Hz.
+    pub sampling: u64,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SubtitleTrack {
+    pub language: Language,
+    /// Like `SRT` or `ASS`.
+    pub format: String,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Language {
+    /// Like `English`.
+    pub name: String,
+    /// Like `eng`.
+    pub iso: String,
+}
+
+impl MediaInfos {
+    /// Reads the `streaming/mediaInfos` response.
+    pub fn from_json(json: &str) -> Result<Self, ApiError> {
+        let infos = serde_json::from_str::<Value>(json).map_err(|e| {
+            ApiError::new(
+                Failure::Api,
+                format!("media infos : invalid response : {e}"),
+            )
+        })?;
+        let details = &infos["details"];
+
+        Ok(Self {
+            filename: text(&infos["filename"]),
+            duration: infos["duration"].as_f64().unwrap_or(0.0),
+            bitrate: infos["bitrate"].as_u64().unwrap_or(0),
+            size: infos["size"].as_u64().unwrap_or(0),
+            video: tracks(&details["video"])
+                .map(|track| VideoTrack {
+                    codec: text(&track["codec"]),
+                    width: track["width"].as_u64().unwrap_or(0),
+                    height: track["height"].as_u64().unwrap_or(0),
+                })
+                .collect(),
+            audio: tracks(&details["audio"])
+                .map(|track| AudioTrack {
+                    language: Language::of(track),
+                    codec: text(&track["codec"]),
+                    channels: track["channels"].as_f64().unwrap_or(0.0),
+                    sampling: track["sampling"].as_u64().unwrap_or(0),
+                })
+                .collect(),
+            subtitles: tracks(&details["subtitles"])
+                .map(|track| SubtitleTrack {
+                    language: Language::of(track),
+                    format: text(&track["type"]),
+                })
+                .collect(),
+        })
+    }
+
+    /// The extension of the file, like `mkv`.
+    pub fn container(&self) -> String {
+        Path::new(&self.filename)
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .unwrap_or_default()
+    }
+
+    /// The resolution of the main video track, like `1080`.
+    pub fn resolution(&self) -> Option<u64> {
+        self.video
+            .first()
+            .and_then(|track| resolution(track.width, track.height))
+    }
+
+    /// Whether an audio track is in `language`, by name or ISO code.
+    pub fn has_audio(&self, language: &str) -> bool {
+        self.audio.iter().any(|track| track.language.is(language))
+    }
+
+    /// A multi-line summary of the file.
+    pub fn summary(&self) -> String {
+        let mut lines = vec![
+            self.filename.clone(),
+            format!("duration   {}", duration(self.duration)),
+            format!("container  {}", self.container()),
+            format!("size       {}", humanize(self.size)),
+            format!("bitrate    {}", bitrate(self.bitrate)),
+        ];
+
+        for track in &self.video {
+            let mut line = format!(
+                "video      {} {}x{}",
+                track.codec, track.width, track.height
+            );
+            if let Some(resolution) = resolution(track.width, track.height) {
+                line.push_str(&format!(" ({resolution}p)"));
+            }
+            lines.push(line);
+        }
+
+        for track in &self.audio {
+            let mut line = format!("audio      {} {}", track.language, track.codec);
+            if track.channels > 0.0 {
+                line.push_str(&format!(" {:.1}", track.channels));
+            }
+            if track.sampling > 0 {
+                line.push_str(&format!(" {} kHz", track.sampling as f64 / 1000.0));
+            }
+            lines.push(line);
+        }
+
+        if !self.subtitles.is_empty() {
+            let subtitles = self
+                .subtitles
+                .iter()
+                .map(|track| format!("{} ({})", track.language, track.format))
+                .collect::<Vec<_>>();
+            lines.push(format!("subtitles  {}", subtitles.join(", ")));
+        }
+
+        lines.join("\n")
+    }
+}
+
+impl Language {
+    fn of(track: &Value) -> Self {
+        Self {
+            name: text(&track["lang"]),
+            iso: text(&track["lang_iso"]),
+        }
+    }
+
+    /// Whether this is `language`, by name or ISO code, ignoring case.
+    pub fn is(&self, language: &str) -> bool {
+        self.name.eq_ignore_ascii_case(language) || self.iso.eq_ignore_ascii_case(language)
+    }
+}
+impl std::fmt::Display for Language {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.iso.as_str(), self.name.as_str()) {
+            ("", "") => write!(f, "und"),
+            (iso, "") | ("", iso) => write!(f, "{iso}"),
+            (iso, name) => write!(f, "{iso} {name}"),
+        }
+    }
+}
+
+/// The media informations of a download, as a summary,
+/// or the raw response with `--output json`.
+pub fn media_infos(id: String) -> ApiResult {
+    let response = streaming::media_infos(id)?;
+
+    match ARGS.output() {
+        Output::Text => Ok(MediaInfos::from_json(&response)?.summary()),
+        Output::Json => Ok(response),
+    }
+}
+
+/// Lists the streamable downloads with an audio track in `audio`
+/// and of the given `resolution`, like `1080p` or `4k`.
+///
+/// Lines are `id  resolution  audio languages  filename`,
+/// or a json array with `--output json`.
+pub fn find(audio: Option<&str>, resolution: Option<&str>) -> ApiResult {
+    let resolution = resolution.map(parse_resolution).transpose()?;
+
+    let downloads =
+        serde_json::from_str::<Value>(&downloads::get_all_downloads()?).unwrap_or_default();
+    let ids = downloads
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|download| download["streamable"] == 1)
+        .filter_map(|download| download["id"].as_str());
+
+    let mut found = Vec::new();
+    for id in ids {
+        let infos = match streaming::media_infos(id.to_string())
+            .and_then(|response| MediaInfos::from_json(&response))
+        {
+            Ok(infos) => infos,
+            Err(e) => {
+                warn!("media infos : `{id}` : {e}");
+                continue;
+            }
+        };
+
+        if audio.is_some_and(|language| !infos.has_audio(language))
+            || resolution.is_some_and(|resolution| infos.resolution() != Some(resolution))
+        {
+            continue;
+        }
+        found.push((id, infos));
+    }
+
+    Ok(match ARGS.output() {
+        Output::Text => found
+            .iter()
+            .map(|(id, infos)| {
+                let resolution = infos
+                    .resolution()
+                    .map(|resolution| format!("{resolution}p"))
+                    .unwrap_or_else(|| String::from("-"));
+                let languages = infos
+                    .audio
+                    .iter()
+                    .map(|track| track.language.to_string())
+                    .collect::<Vec<_>>();
+
+                format!(
+                    "{id}\t{resolution:>5}\t{}\t{}",
+                    languages.join(","),
+                    infos.filename
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Output::Json => found
+            .iter()
+            .map(|(id, infos)| {
+                json!({
+                    "id": id,
+                    "filename": infos.filename,
+                    "resolution": infos.resolution(),
+                    "audio": infos.audio.iter().map(|track| &track.language.iso).collect::<Vec<_>>(),
+                })
+            })
+            .collect::<Value>()
+            .to_string(),
+    })
+}
+
+/// Reads a resolution like `1080`, `1080p` or `4k` into its height.
+fn parse_resolution(resolution: &str) -> Result<u64, ApiError> {
+    let resolution = resolution.trim().to_lowercase();
+
+    match resolution.as_str() {
+        "4k" | "uhd" => Ok(2160),
+        "2k" => Ok(1440),
+        "hd" => Ok(720),
+        "fhd" => Ok(1080),
+        _ => resolution
+            .trim_end_matches(['p', 'i'])
+            .parse()
+            .map_err(|_| {
+                ApiError::new(
+                    Failure::InvalidInput,
+                    format!("invalid resolution `{resolution}`, e.g. `1080p` or `4k`"),
+                )
+            }),
+    }
+}
+
+/// The standard resolution of a video, so a cropped `1920x800` is `1080`.
+fn resolution(width: u64, height: u64) -> Option<u64> {
+    RESOLUTIONS
+        .iter()
+        .find(|(standard_width, standard_height)| {
+            width * 10 >= standard_width * 9 || height * 10 >= standard_height * 9
+        })
+        .map(|&(_, standard_height)| standard_height)
+        .or((height > 0).then_some(height))
+}
+
+/// The tracks of a kind, given as an object by track name or as an array.
+fn tracks(tracks: &Value) -> impl Iterator<Item = &Value> {
+    let tracks: Vec<&Value> = match tracks {
+        Value::Object(tracks) => tracks.values().collect(),
+        Value::Array(tracks) => tracks.iter().collect(),
+        _ => Vec::new(),
+    };
+
+    tracks.into_iter()
+}
+
+fn text(value: &Value) -> String {
+    value.as_str().unwrap_or_default().to_string()
+}
+
+/// Formats seconds as `h:mm:ss`.
+fn duration(seconds: f64) -> String {
+    let seconds = seconds.round() as u64;
+
+    format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// Formats bits per second as `8.2 Mb/s`.
+fn bitrate(bits: u64) -> String {
+    match bits {
+        0 => String::from("-"),
+        1..1_000_000 => format!("{} kb/s", bits / 1000),
+        _ => format!("{:.1} Mb/s", bits as f64 / 1_000_000.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolutions_are_parsed() {
+        assert_eq!(parse_resolution("1080p").ok(), Some(1080));
+        assert_eq!(parse_resolution("4K").ok(), Some(2160));
+        assert_eq!(parse_resolution("576i").ok(), Some(576));
+        assert!(parse_resolution("high").is_err());
+    }
+
+    #[test]
+    fn cropped_videos_keep_their_resolution() {
+        assert_eq!(resolution(1920, 800), Some(1080));
+        assert_eq!(resolution(3840, 1600), Some(2160));
+        assert_eq!(resolution(720, 576), Some(576));
+        assert_eq!(resolution(320, 240), Some(240));
+        assert_eq!(resolution(0, 0), None);
+    }
+}
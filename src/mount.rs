@@ -0,0 +1,886 @@
+//! # Mount Module
+//!
+//! This module mounts the downloaded torrents and downloads
+//! as a read-only file system, speaking the FUSE protocol on `/dev/fuse`.
+//!
+//! The file system is mounted by `fusermount3`, like other FUSE file systems,
+//! or directly by root when it is not installed.
+//!
+//! ```text
+//! <dir>/torrents/<torrent name>/<file name>
+//! <dir>/downloads/<file name>
+//! ```
+//!
+//! Torrent folders hold the selected files of the torrent, see the `library` module.
+//! Files are streamed on demand from their unrestricted link: every read fetches
+//! at least `--read-ahead` MiB, which serves the following sequential reads.
+//!
+//! The file system stays mounted until interrupted, or unmounted with `fusermount3 -u`.
+
+use std::collections::{HashMap, HashSet};
+use std::ffi::CString;
+use std::fs::{File as DevFile, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::fs::OpenOptionsExt;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, mpsc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use reqwest::StatusCode;
+use reqwest::header::RANGE;
+use serde_json::Value;
+
+use crate::date;
+use crate::downloads;
+use crate::error::{ApiError, Failure};
+use crate::fetch::{DOWNLOAD_CLIENT, sanitize_filename};
+use crate::library::{Cache, File, Folder, LISTING_TTL, Links};
+
+/// The protocol version spoken.
+const FUSE_MAJOR: u32 = 7;
+const FUSE_MINOR: u32 = 31;
+
+const MAX_WRITE: usize = 128 * 1024;
+/// Room for the largest request the kernel sends.
+const BUFFER_SIZE: usize = MAX_WRITE + 4096;
+
+const IN_HEADER: usize = 40;
+const OUT_HEADER: usize = 16;
+
+// Opcodes.
+const LOOKUP: u32 = 1;
+const FORGET: u32 = 2;
+const GETATTR: u32 = 3;
+const OPEN: u32 = 14;
+const READ: u32 = 15;
+const STATFS: u32 = 17;
+const RELEASE: u32 = 18;
+const GETXATTR: u32 = 22;
+const LISTXATTR: u32 = 23;
+const FLUSH: u32 = 25;
+const INIT: u32 = 26;
+const OPENDIR: u32 = 27;
+const READDIR: u32 = 28;
+const RELEASEDIR: u32 = 29;
+const ACCESS: u32 = 34;
+const INTERRUPT: u32 = 36;
+const DESTROY: u32 = 38;
+const BATCH_FORGET: u32 = 42;
+
+const FUSE_ASYNC_READ: u32 = 1 << 0;
+const FOPEN_KEEP_CACHE: u32 = 1 << 1;
+
+/// The helpers mounting for unprivileged users, by preference.
+const HELPERS: [&str; 2] = ["fusermount3", "fusermount"];
+const MOUNT_OPTIONS: &str = "ro,nosuid,nodev,fsname=traffic_cone,subtype=traffic_cone";
+
+const ROOT: u64 = 1;
+const TORRENTS: u64 = 2;
+const DOWNLOADS: u64 = 3;
+
+/// How the file system was mounted, and so how it is unmounted.
+enum Mounter {
+    /// By a setuid helper.
+    Helper(&'static str),
+    /// By the `mount` system call.
+    Kernel,
+}
+
+/// An entry of the file tree.
+#[derive(Clone)]
+enum Node {
+    Root,
+    Torrents,
+    Downloads,
+    Folder(Folder),
+    /// A file, with the seconds since the unix epoch it was added at.
+    File(File, i64),
+}
+
+/// What identifies a node across listings, so it keeps its inode.
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Key {
+    Folder(String),
+    File(String, String),
+    Download(String),
+}
+
+#[derive(Default)]
+struct Inodes {
+    by_key: HashMap<Key, u64>,
+    nodes: HashMap<u64, Node>,
+}
+
+/// A download, by its id.
+#[derive(Clone)]
+struct Download {
+    id: String,
+    file: File,
+    /// Seconds since the unix epoch.
+    generated: i64,
+}
+
+/// An open file, with the last window fetched from it.
+struct Handle {
+    file: File,
+    window: Option<(u64, Vec<u8>)>,
+}
+
+struct Mount {
+    device: DevFile,
+    uid: u32,
+    gid: u32,
+    read_ahead: u64,
+    links: Links,
+    inodes: Mutex<Inodes>,
+    library: Cache,
+    downloads: Mutex<Option<(Instant, Vec<Download>)>>,
+    handles: Mutex<HashMap<u64, Arc<Mutex<Handle>>>>,
+    next_handle: AtomicU64,
+}
+
+/// A request of the kernel.
+struct Request {
+    opcode: u32,
+    unique: u64,
+    node: u64,
+    arg: Vec<u8>,
+}
+
+/// Mounts the file tree on `dir` until it is unmounted.
+pub fn run(dir: &str, link_ttl: u64, read_ahead: u64, workers: usize) -> Result<(), ApiError> {
+    let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+    let (device, mounter) = mount(dir, uid, gid)?;
+    info!("mount : `{dir}` mounted");
+
+    let mount = Mount {
+        device,
+        uid,
+        gid,
+        read_ahead: read_ahead * 1024 * 1024,
+        links: Links::new(Duration::from_secs(link_ttl)),
+        inodes: Mutex::new(Inodes::default()),
+        library: Cache::default(),
+        downloads: Mutex::new(None),
+        handles: Mutex::new(HashMap::new()),
+        next_handle: AtomicU64::new(1),
+    };
+
+    unmount_on_signal(dir.to_string(), mounter);
+
+    // Requests are read here and handled by `workers` threads,
+    // which stop once the sender is dropped.
+    let (sender, receiver) = mpsc::sync_channel::<Request>(workers);
+    let receiver = Mutex::new(receiver);
+    thread::scope(|scope| {
+        let sender = sender;
+        for _ in 0..workers.max(1) {
+            scope.spawn(|| {
+                loop {
+                    let request = receiver.lock().unwrap_or_else(|e| e.into_inner()).recv();
+                    let Ok(request) = request else {
+                        break;
+                    };
+                    mount.handle(request);
+                }
+            });
+        }
+
+        let mut buffer = vec![0; BUFFER_SIZE];
+        loop {
+            let length = match (&mount.device).read(&mut buffer) {
+                Ok(length) => length,
+                // The request was interrupted before it was read.
+                Err(e) if matches!(e.raw_os_error(), Some(libc::ENOENT | libc::EINTR)) => {
+                    continue;
+                }
+                Err(e) if e.raw_os_error() == Some(libc::ENODEV) => return Ok(()),
+                Err(e) => return Err(ApiError::new(Failure::Api, format!("/dev/fuse : {e}"))),
+            };
+
+            let Some(request) = Request::parse(&buffer[..length]) else {
+                warn!("mount : short request of {length} bytes");
+                continue;
+            };
+
+            match request.opcode {
+                INIT => mount.init(&request),
+                FORGET | BATCH_FORGET | INTERRUPT => {}
+                DESTROY => {
+                    mount.reply(&request, Ok(Vec::new()));
+                    return Ok(());
+                }
+                _ => {
+                    let _ = sender.send(request);
+                }
+            }
+        }
+    })?;
+
+    info!("mount : `{dir}` unmounted");
+
+    Ok(())
+}
+
+/// Mounts `dir`, returning the connection to the kernel.
+///
+/// Like other FUSE file systems, the mount goes through the setuid `fusermount3`
+/// helper, so users can mount without privileges. Without it, only root can mount.
+fn mount(dir: &str, uid: u32, gid: u32) -> Result<(DevFile, Mounter), ApiError> {
+    for helper in HELPERS {
+        match mount_with_helper(helper, dir) {
+            Ok(device) => return Ok((device, Mounter::Helper(helper))),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                debug!("mount : `{helper}` not found");
+            }
+            Err(e) => {
+                return Err(ApiError::new(
+                    Failure::InvalidInput,
+                    format!("mount `{dir}` : {helper} : {e}"),
+                ));
+            }
+        }
+    }
+
+    mount_directly(dir, uid, gid).map(|device| (device, Mounter::Kernel))
+}
+
+/// Mounts `dir` with a helper, which sends back the opened `/dev/fuse`
+/// over the socket given in `_FUSE_COMMFD`.
+///
+/// A missing helper is reported as `NotFound`.
+fn mount_with_helper(helper: &str, dir: &str) -> io::Result<DevFile> {
+    let mut sockets = [0; 2];
+    if unsafe { libc::socketpair(libc::AF_UNIX, libc::SOCK_STREAM, 0, sockets.as_mut_ptr()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let (ours, theirs) = unsafe {
+        (
+            OwnedFd::from_raw_fd(sockets[0]),
+            OwnedFd::from_raw_fd(sockets[1]),
+        )
+    };
+    // Only the end of the helper is inherited.
+    unsafe { libc::fcntl(ours.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC) };
+
+    let child = Command::new(helper)
+        .args(["-o", MOUNT_OPTIONS, "--", dir])
+        .env("_FUSE_COMMFD", theirs.as_raw_fd().to_string())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    // The socket reads the end of the file once the helper exits.
+    drop(theirs);
+
+    let device = receive_fd(&ours);
+    let output = child.wait_with_output()?;
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+
+    match device {
+        Ok(device) if output.status.success() => Ok(DevFile::from(device)),
+        _ if !stderr.is_empty() => Err(io::Error::other(stderr)),
+        Err(e) => Err(e),
+        Ok(_) => Err(io::Error::other(format!("exited with {}", output.status))),
+    }
+}
+
+/// Receives a file descriptor sent with `SCM_RIGHTS`.
+fn receive_fd(socket: &OwnedFd) -> io::Result<OwnedFd> {
+    let mut byte = [0u8; 1];
+    let mut iov = libc::iovec {
+        iov_base: byte.as_mut_ptr().cast(),
+        iov_len: byte.len(),
+    };
+    // Aligned room for the control message of one descriptor.
+    let mut control = [0u64; 8];
+
+    let mut message = unsafe { std::mem::zeroed::<libc::msghdr>() };
+    message.msg_iov = &mut iov;
+    message.msg_iovlen = 1;
+    message.msg_control = control.as_mut_ptr().cast();
+    message.msg_controllen = std::mem::size_of_val(&control) as _;
+
+    if unsafe { libc::recvmsg(socket.as_raw_fd(), &mut message, 0) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let header = unsafe { libc::CMSG_FIRSTHDR(&message) };
+    if header.is_null()
+        || unsafe { (*header).cmsg_level } != libc::SOL_SOCKET
+        || unsafe { (*header).cmsg_type } != libc::SCM_RIGHTS
+    {
+        return Err(io::Error::other("no file descriptor received"));
+    }
+
+    let fd = unsafe { std::ptr::read_unaligned(libc::CMSG_DATA(header).cast::<RawFd>()) };
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+/// Mounts `dir` with the `mount` system call, which takes `CAP_SYS_ADMIN`.
+fn mount_directly(dir: &str, uid: u32, gid: u32) -> Result<DevFile, ApiError> {
+    let device = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_CLOEXEC)
+        .open("/dev/fuse")
+        .map_err(|e| ApiError::new(Failure::InvalidInput, format!("/dev/fuse : {e}")))?;
+
+    let target = CString::new(dir)
+        .map_err(|_| ApiError::new(Failure::InvalidInput, format!("mount : bad path `{dir}`")))?;
+    let options = CString::new(format!(
+        "fd={},rootmode=40000,user_id={uid},group_id={gid}",
+        device.as_raw_fd()
+    ))
+    .unwrap_or_default();
+
+    let mounted = unsafe {
+        libc::mount(
+            c"traffic_cone".as_ptr(),
+            target.as_ptr(),
+            c"fuse.traffic_cone".as_ptr(),
+            libc::MS_NOSUID | libc::MS_NODEV | libc::MS_RDONLY,
+            options.as_ptr().cast(),
+        )
+    };
+    if mounted != 0 {
+        let e = io::Error::last_os_error();
+        return Err(match e.raw_os_error() {
+            Some(libc::EPERM) => ApiError::new(
+                Failure::InvalidInput,
+                format!("mount `{dir}` : {e}, install `fusermount3` (fuse3) or mount as root"),
+            ),
+            Some(libc::ENOENT | libc::ENOTDIR) => {
+                ApiError::new(Failure::InvalidInput, format!("mount `{dir}` : {e}"))
+            }
+            _ => ApiError::new(Failure::Api, format!("mount `{dir}` : {e}")),
+        });
+    }
+
+    Ok(device)
+}
+
+/// Unmounts `dir` the way it was mounted.
+fn unmount(dir: &str, mounter: &Mounter) -> io::Result<()> {
+    match mounter {
+        Mounter::Helper(helper) => {
+            let status = Command::new(helper)
+                .args(["-u", "-z", "--", dir])
+                .status()?;
+            match status.success() {
+                true => Ok(()),
+                false => Err(io::Error::other(format!("{helper} exited with {status}"))),
+            }
+        }
+        Mounter::Kernel => {
+            let target = CString::new(dir).map_err(io::Error::other)?;
+            match unsafe { libc::umount2(target.as_ptr(), libc::MNT_DETACH) } {
+                0 => Ok(()),
+                _ => Err(io::Error::last_os_error()),
+            }
+        }
+    }
+}
+
+/// Unmounts `dir` on `SIGINT` or `SIGTERM`, which ends the request loop.
+fn unmount_on_signal(dir: String, mounter: Mounter) {
+    // Blocked signals are inherited by every thread spawned from now on,
+    // so only the waiting thread receives them.
+    let signals = unsafe {
+        let mut signals = std::mem::zeroed::<libc::sigset_t>();
+        libc::sigemptyset(&mut signals);
+        libc::sigaddset(&mut signals, libc::SIGINT);
+        libc::sigaddset(&mut signals, libc::SIGTERM);
+        libc::pthread_sigmask(libc::SIG_BLOCK, &signals, std::ptr::null_mut());
+        signals
+    };
+
+    thread::spawn(move || {
+        let mut signal = 0;
+        unsafe { libc::sigwait(&signals, &mut signal) };
+
+        debug!("mount : signal {signal}, unmounting");
+        if let Err(e) = unmount(&dir, &mounter) {
+            error!("mount : unmount : {e}");
+            std::process::exit(Failure::Api.exit_code());
+        }
+    });
+}
+
+impl Request {
+    fn parse(message: &[u8]) -> Option<Self> {
+        let header = message.get(..IN_HEADER)?;
+
+        Some(Self {
+            opcode: u32_at(header, 4),
+            unique: u64_at(header, 8),
+            node: u64_at(header, 16),
+            arg: message[IN_HEADER..].to_vec(),
+        })
+    }
+}
+
+impl Mount {
+    /// Writes the reply to a request, an error being a positive errno.
+    fn reply(&self, request: &Request, reply: Result<Vec<u8>, i32>) {
+        let (error, payload) = match reply {
+            Ok(payload) => (0, payload),
+            Err(errno) => (-errno, Vec::new()),
+        };
+
+        let mut message = Vec::with_capacity(OUT_HEADER + payload.len());
+        message.extend_from_slice(&((OUT_HEADER + payload.len()) as u32).to_ne_bytes());
+        message.extend_from_slice(&error.to_ne_bytes());
+        message.extend_from_slice(&request.unique.to_ne_bytes());
+        message.extend_from_slice(&payload);
+
+        // The request may have been interrupted meanwhile, which is not an error.
+        if let Err(e) = (&self.device).write(&message)
+            && e.raw_os_error() != Some(libc::ENOENT)
+        {
+            warn!("mount : reply to opcode {} : {e}", request.opcode);
+        }
+    }
+
+    fn init(&self, request: &Request) {
+        let major = u32_at(&request.arg, 0);
+        let max_readahead = u32_at(&request.arg, 8);
+        let flags = u32_at(&request.arg, 12);
+
+        if major < FUSE_MAJOR {
+            error!("mount : kernel FUSE protocol {major} is unsupported");
+            return self.reply(request, Err(libc::EPROTO));
+        }
+
+        let mut out = Vec::with_capacity(64);
+        push_u32(&mut out, FUSE_MAJOR);
+        push_u32(&mut out, FUSE_MINOR);
+        push_u32(&mut out, max_readahead);
+        push_u32(&mut out, flags & FUSE_ASYNC_READ);
+        out.extend_from_slice(&16u16.to_ne_bytes()); // max_background
+        out.extend_from_slice(&12u16.to_ne_bytes()); // congestion_threshold
+        push_u32(&mut out, MAX_WRITE as u32);
+        push_u32(&mut out, 1); // time_gran
+        out.resize(64, 0);
+
+        self.reply(request, Ok(out));
+    }
+
+    fn handle(&self, request: Request) {
+        let reply = match request.opcode {
+            LOOKUP => self.lookup(&request),
+            GETATTR => self.node(request.node).map(|node| {
+                let mut out = Vec::with_capacity(104);
+                push_u64(&mut out, ttl(&node).as_secs());
+                push_u32(&mut out, 0);
+                push_u32(&mut out, 0);
+                self.push_attr(&mut out, request.node, &node);
+                out
+            }),
+            OPEN => self.open(&request),
+            READ => self.read(&request),
+            RELEASE => {
+                self.handles
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .remove(&u64_at(&request.arg, 0));
+                Ok(Vec::new())
+            }
+            OPENDIR => match self.node(request.node) {
+                Ok(Node::File(..)) => Err(libc::ENOTDIR),
+                Ok(_) => Ok(vec![0; 16]),
+                Err(errno) => Err(errno),
+            },
+            READDIR => self.readdir(&request),
+            RELEASEDIR | FLUSH => Ok(Vec::new()),
+            ACCESS if u32_at(&request.arg, 0) & libc::W_OK as u32 != 0 => Err(libc::EROFS),
+            ACCESS => Ok(Vec::new()),
+            STATFS => {
+                let mut out = vec![0; 40];
+                push_u32(&mut out, 4096); // bsize
+                push_u32(&mut out, 255); // namelen
+                push_u32(&mut out, 4096); // frsize
+                out.resize(80, 0);
+                Ok(out)
+            }
+            GETXATTR | LISTXATTR => Err(libc::ENOSYS),
+            opcode => {
+                debug!("mount : unsupported opcode {opcode}");
+                Err(libc::ENOSYS)
+            }
+        };
+
+        self.reply(&request, reply);
+    }
+
+    fn node(&self, inode: u64) -> Result<Node, i32> {
+        match inode {
+            ROOT => Ok(Node::Root),
+            TORRENTS => Ok(Node::Torrents),
+            DOWNLOADS => Ok(Node::Downloads),
+            _ => self
+                .inodes
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .nodes
+                .get(&inode)
+                .cloned()
+                .ok_or(libc::ENOENT),
+        }
+    }
+
+    /// The inode of a node, stable for as long as the file system is mounted.
+    fn inode(&self, key: Key, node: Node) -> u64 {
+        let mut inodes = self.inodes.lock().unwrap_or_else(|e| e.into_inner());
+        let next = inodes.by_key.len() as u64 + DOWNLOADS + 1;
+        let inode = *inodes.by_key.entry(key).or_insert(next);
+        inodes.nodes.insert(inode, node);
+
+        inode
+    }
+
+    /// The entries of a directory, by name.
+    fn children(&self, inode: u64) -> Result<Vec<(String, u64, Node)>, i32> {
+        let children = match self.node(inode)? {
+            Node::Root => vec![
+                (String::from("torrents"), TORRENTS, Node::Torrents),
+                (String::from("downloads"), DOWNLOADS, Node::Downloads),
+            ],
+            Node::Torrents => self
+                .library
+                .folders()
+                .map_err(io_error)?
+                .into_iter()
+                .map(|folder| {
+                    let node = Node::Folder(folder.clone());
+                    let inode = self.inode(Key::Folder(folder.id.clone()), node.clone());
+                    (folder.name, inode, node)
+                })
+                .collect(),
+            Node::Downloads => self
+                .downloads()
+                .map_err(io_error)?
+                .into_iter()
+                .map(|download| {
+                    let node = Node::File(download.file.clone(), download.generated);
+                    let inode = self.inode(Key::Download(download.id), node.clone());
+                    (download.file.name, inode, node)
+                })
+                .collect(),
+            Node::Folder(folder) => self
+                .library
+                .files(&folder.id)
+                .map_err(io_error)?
+                .into_iter()
+                .map(|file| {
+                    let key = Key::File(folder.id.clone(), file.name.clone());
+                    let node = Node::File(file.clone(), folder.added);
+                    let inode = self.inode(key, node.clone());
+                    (file.name, inode, node)
+                })
+                .collect(),
+            Node::File(..) => return Err(libc::ENOTDIR),
+        };
+
+        Ok(children)
+    }
+
+    fn lookup(&self, request: &Request) -> Result<Vec<u8>, i32> {
+        let name = request
+            .arg
+            .split(|&byte| byte == 0)
+            .next()
+            .unwrap_or_default();
+        let name = String::from_utf8_lossy(name);
+
+        let (_, inode, node) = self
+            .children(request.node)?
+            .into_iter()
+            .find(|(child, ..)| *child == name)
+            .ok_or(libc::ENOENT)?;
+
+        let ttl = ttl(&node).as_secs();
+        let mut out = Vec::with_capacity(128);
+        push_u64(&mut out, inode);
+        push_u64(&mut out, 0); // generation
+        push_u64(&mut out, ttl); // entry_valid
+        push_u64(&mut out, ttl); // attr_valid
+        push_u32(&mut out, 0);
+        push_u32(&mut out, 0);
+        self.push_attr(&mut out, inode, &node);
+
+        Ok(out)
+    }
+
+    fn readdir(&self, request: &Request) -> Result<Vec<u8>, i32> {
+        let offset = u64_at(&request.arg, 8) as usize;
+        let size = u32_at(&request.arg, 16) as usize;
+
+        let parent = match self.node(request.node)? {
+            Node::Folder(_) => TORRENTS,
+            _ => ROOT,
+        };
+        let mut entries = vec![
+            (String::from("."), request.node, libc::DT_DIR),
+            (String::from(".."), parent, libc::DT_DIR),
+        ];
+        entries.extend(
+            self.children(request.node)?
+                .into_iter()
+                .map(|(name, inode, node)| {
+                    let kind = match node {
+                        Node::File(..) => libc::DT_REG,
+                        _ => libc::DT_DIR,
+                    };
+                    (name, inode, kind)
+                }),
+        );
+
+        let mut out = Vec::new();
+        for (index, (name, inode, kind)) in entries.into_iter().enumerate().skip(offset) {
+            let entry = 24 + name.len();
+            let padded = entry.next_multiple_of(8);
+            if out.len() + padded > size {
+                break;
+            }
+
+            push_u64(&mut out, inode);
+            push_u64(&mut out, index as u64 + 1);
+            push_u32(&mut out, name.len() as u32);
+            push_u32(&mut out, kind as u32);
+            out.extend_from_slice(name.as_bytes());
+            out.resize(out.len() + padded - entry, 0);
+        }
+
+        Ok(out)
+    }
+
+    fn open(&self, request: &Request) -> Result<Vec<u8>, i32> {
+        let flags = u32_at(&request.arg, 0) as i32;
+        if flags & libc::O_ACCMODE != libc::O_RDONLY {
+            return Err(libc::EROFS);
+        }
+
+        let Node::File(file, _) = self.node(request.node)? else {
+            return Err(libc::EISDIR);
+        };
+
+        let handle = self.next_handle.fetch_add(1, Ordering::Relaxed);
+        self.handles
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(handle, Arc::new(Mutex::new(Handle { file, window: None })));
+
+        let mut out = Vec::with_capacity(16);
+        push_u64(&mut out, handle);
+        push_u32(&mut out, FOPEN_KEEP_CACHE);
+        push_u32(&mut out, 0);
+
+        Ok(out)
+    }
+
+    /// Reads from the window of the handle, fetching a new one when needed.
+    fn read(&self, request: &Request) -> Result<Vec<u8>, i32> {
+        let handle = u64_at(&request.arg, 0);
+        let offset = u64_at(&request.arg, 8);
+        let size = u32_at(&request.arg, 16) as u64;
+
+        let handle = self
+            .handles
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&handle)
+            .cloned()
+            .ok_or(libc::EBADF)?;
+        let mut handle = handle.lock().unwrap_or_else(|e| e.into_inner());
+
+        let end = (offset + size).min(handle.file.bytes);
+        if offset >= end {
+            return Ok(Vec::new());
+        }
+
+        let cached = handle
+            .window
+            .as_ref()
+            .is_some_and(|(start, data)| *start <= offset && end <= *start + data.len() as u64);
+        if !cached {
+            let length = (end - offset).max(self.read_ahead);
+            let data = self.fetch(&handle.file, offset, length).map_err(|e| {
+                warn!("mount : read `{}` : {e}", handle.file.name);
+                libc::EIO
+            })?;
+            handle.window = Some((offset, data));
+        }
+
+        let (start, data) = handle.window.as_ref().ok_or(libc::EIO)?;
+        let from = (offset - start) as usize;
+        let to = ((end - start) as usize).min(data.len());
+
+        Ok(data.get(from..to).unwrap_or_default().to_vec())
+    }
+
+    /// Fetches `length` bytes of a file from `offset`.
+    ///
+    /// An expired link is unrestricted again, once.
+    fn fetch(&self, file: &File, offset: u64, length: u64) -> Result<Vec<u8>, ApiError> {
+        let last = (offset + length).min(file.bytes).saturating_sub(1);
+
+        for _ in 0..2 {
+            let url = self.links.download_url(file)?;
+            let response = DOWNLOAD_CLIENT
+                .get(url)
+                .header(RANGE, format!("bytes={offset}-{last}"))
+                .send()?;
+
+            let status = response.status();
+            if status.is_client_error() && status != StatusCode::RANGE_NOT_SATISFIABLE {
+                debug!("mount : `{}` : link expired", file.name);
+                self.links.expire(file);
+                continue;
+            }
+            if !status.is_success() {
+                return Err(ApiError::new(
+                    Failure::Network,
+                    format!("fetch `{}` : {status}", file.name),
+                ));
+            }
+
+            let mut response = response;
+            // A server ignoring the range sends the whole file.
+            if status != StatusCode::PARTIAL_CONTENT {
+                io::copy(&mut (&mut response).take(offset), &mut io::sink())
+                    .map_err(|e| ApiError::new(Failure::Network, e.to_string()))?;
+            }
+
+            let mut data = Vec::with_capacity((last + 1 - offset) as usize);
+            response
+                .take(last + 1 - offset)
+                .read_to_end(&mut data)
+                .map_err(|e| ApiError::new(Failure::Network, e.to_string()))?;
+
+            return Ok(data);
+        }
+
+        Err(ApiError::new(
+            Failure::Network,
+            format!("fetch `{}` : link expired", file.name),
+        ))
+    }
+
+    fn push_attr(&self, out: &mut Vec<u8>, inode: u64, node: &Node) {
+        let (size, mode, nlink, time) = match node {
+            Node::File(file, added) => (file.bytes, libc::S_IFREG | 0o444, 1, *added),
+            Node::Folder(folder) => (0, libc::S_IFDIR | 0o555, 2, folder.added),
+            _ => (0, libc::S_IFDIR | 0o555, 2, 0),
+        };
+
+        push_u64(out, inode);
+        push_u64(out, size);
+        push_u64(out, size.div_ceil(512)); // blocks
+        for _ in 0..3 {
+            push_u64(out, time.max(0) as u64); // atime, mtime, ctime
+        }
+        for _ in 0..3 {
+            push_u32(out, 0); // nanoseconds
+        }
+        push_u32(out, mode);
+        push_u32(out, nlink);
+        push_u32(out, self.uid);
+        push_u32(out, self.gid);
+        push_u32(out, 0); // rdev
+        push_u32(out, 4096); // blksize
+        push_u32(out, 0); // flags
+    }
+
+    /// The downloads with unique file names, reused for `LISTING_TTL`.
+    ///
+    /// Their unrestricted links are remembered, so reading them unrestricts nothing.
+    fn downloads(&self) -> Result<Vec<Download>, ApiError> {
+        let mut listing = self.downloads.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((at, downloads)) = listing.as_ref()
+            && at.elapsed() < LISTING_TTL
+        {
+            return Ok(downloads.clone());
+        }
+
+        let entries =
+            serde_json::from_str::<Value>(&downloads::get_all_downloads()?).unwrap_or_default();
+
+        let mut names = HashSet::new();
+        let downloads = entries
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|download| {
+                let id = download["id"].as_str()?.to_string();
+                let mut name = sanitize_filename(download["filename"].as_str().unwrap_or(&id));
+                if !names.insert(name.clone()) {
+                    name = format!("{id} {name}");
+                }
+
+                let file = File {
+                    name,
+                    bytes: download["filesize"].as_u64().unwrap_or(0),
+                    link: download["link"].as_str().unwrap_or_default().to_string(),
+                };
+                if let Some(url) = download["download"].as_str() {
+                    self.links.remember(&file, url);
+                }
+                let generated = download["generated"]
+                    .as_str()
+                    .and_then(date::parse_rfc3339)
+                    .unwrap_or(0);
+
+                Some(Download {
+                    id,
+                    file,
+                    generated,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        *listing = Some((Instant::now(), downloads.clone()));
+
+        Ok(downloads)
+    }
+}
+
+/// How long the kernel may cache a node.
+fn ttl(node: &Node) -> Duration {
+    match node {
+        Node::File(..) => Duration::from_secs(3600),
+        _ => LISTING_TTL,
+    }
+}
+
+fn io_error(e: ApiError) -> i32 {
+    warn!("mount : {e}");
+    libc::EIO
+}
+
+fn push_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_ne_bytes());
+}
+
+fn push_u64(out: &mut Vec<u8>, value: u64) {
+    out.extend_from_slice(&value.to_ne_bytes());
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> u32 {
+    bytes
+        .get(at..at + 4)
+        .and_then(|bytes| bytes.try_into().ok())
+        .map(u32::from_ne_bytes)
+        .unwrap_or(0)
+}
+
+fn u64_at(bytes: &[u8], at: usize) -> u64 {
+    bytes
+        .get(at..at + 8)
+        .and_then(|bytes| bytes.try_into().ok())
+        .map(u64::from_ne_bytes)
+        .unwrap_or(0)
+}
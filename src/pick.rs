@@ -0,0 +1,217 @@
+//! # Pick Module
+//!
+//! This module resolves the torrent and download ids given to commands.
+//!
+//! An id can be given as:
+//! - the id itself,
+//! - a case-insensitive substring of the filename,
+//! - nothing at all, which opens an interactive picker on a terminal.
+//!
+//! A query shaped like an id, 13 uppercase letters or digits, is used as is,
+//! without fetching the listing.
+//! Otherwise it is looked up in the whole listing, across every page.
+//! When a substring matches several items, the picker is opened on the matches,
+//! and when it matches nothing, it fails with `NotFound`.
+//!
+//! ## Picker
+//!
+//! The picker lists the items on stderr and reads from stdin.
+//! Typing text fuzzy-filters the list by filename,
+//! typing a number picks that entry, and an empty line picks
+//! the only remaining entry.
+
+use std::io::{BufRead, IsTerminal, Write};
+
+use serde_json::Value;
+
+use crate::error::{ApiError, Failure};
+use crate::{ApiResult, downloads, torrents};
+
+/// The most entries the picker prints at once.
+const PAGE_SIZE: usize = 20;
+
+/// The length of torrent and download ids.
+const ID_LENGTH: usize = 13;
+
+type Id = String;
+
+/// An `(id, filename)` pair from a listing.
+type Item = (Id, String);
+
+/// Resolves a torrent id.
+pub(crate) fn torrent(query: Option<String>) -> Result<Id, ApiError> {
+    resolve("torrent", torrents::get_all_torrents, query)
+}
+
+/// Resolves a download id.
+pub(crate) fn download(query: Option<String>) -> Result<Id, ApiError> {
+    resolve("download", downloads::get_all_downloads, query)
+}
+
+fn resolve(
+    kind: &str,
+    listing: impl FnOnce() -> ApiResult,
+    query: Option<String>,
+) -> Result<Id, ApiError> {
+    let Some(query) = query else {
+        require_interactive(kind)?;
+        return pick(kind, parse_listing(&listing()?));
+    };
+
+    if looks_like_id(&query) {
+        return Ok(query);
+    }
+
+    let items = parse_listing(&listing()?);
+
+    if items.iter().any(|(id, _)| *id == query) {
+        return Ok(query);
+    }
+
+    let needle = query.to_lowercase();
+    let mut matches = items
+        .into_iter()
+        .filter(|(_, filename)| filename.to_lowercase().contains(&needle))
+        .collect::<Vec<_>>();
+
+    match matches.len() {
+        0 => Err(ApiError::new(
+            Failure::NotFound,
+            format!("`{query}` matches no {kind}"),
+        )),
+        1 => {
+            let (id, filename) = matches.remove(0);
+            info!("{kind} : `{query}` matched `{filename}` ({id})");
+            Ok(id)
+        }
+        count if interactive() => {
+            info!("{kind} : `{query}` matched {count} {kind}s");
+            pick(kind, matches)
+        }
+        count => Err(ApiError::new(
+            Failure::InvalidInput,
+            format!("`{query}` matches {count} {kind}s, give an id or a longer filename"),
+        )),
+    }
+}
+
+/// Whether a query is shaped like an id, like `ABCDEFGH12345`.
+fn looks_like_id(query: &str) -> bool {
+    query.len() == ID_LENGTH
+        && query
+            .bytes()
+            .all(|byte| byte.is_ascii_uppercase() || byte.is_ascii_digit())
+}
+
+/// Reads the `(id, filename)` pairs of a torrents or downloads listing.
+fn parse_listing(listing: &str) -> Vec<Item> {
+    let listing = serde_json::from_str::<Value>(listing).unwrap_or_default();
+
+    listing
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|item| {
+            Some((
+                item["id"].as_str()?.to_string(),
+                item["filename"].as_str()?.to_string(),
+            ))
+        })
+        .collect()
+}
+
+/// Whether a picker can be shown.
+fn interactive() -> bool {
+    std::io::stdin().is_terminal() && std::io::stderr().is_terminal()
+}
+
+/// Whether every character of `needle` appears in `haystack`, in order.
+fn fuzzy_match(haystack: &str, needle: &str) -> bool {
+    let mut haystack = haystack.chars().flat_map(char::to_lowercase);
+
+    needle
+        .chars()
+        .flat_map(char::to_lowercase)
+        .all(|wanted| haystack.any(|found| found == wanted))
+}
+
+/// Fails unless a picker can be shown.
+fn require_interactive(kind: &str) -> Result<(), ApiError> {
+    if interactive() {
+        Ok(())
+    } else {
+        Err(ApiError::new(
+            Failure::InvalidInput,
+            format!("no {kind} id given"),
+        ))
+    }
+}
+
+/// Interactively picks one of `items`.
+fn pick(kind: &str, items: Vec<Item>) -> Result<Id, ApiError> {
+    if items.is_empty() {
+        return Err(ApiError::new(
+            Failure::NotFound,
+            format!("no {kind}s to pick from"),
+        ));
+    }
+
+    let mut stderr = std::io::stderr();
+    let mut lines = std::io::stdin().lock().lines();
+    let mut filter = String::new();
+
+    loop {
+        let shown = items
+            .iter()
+            .filter(|(_, filename)| fuzzy_match(filename, &filter))
+            .collect::<Vec<_>>();
+
+        for (index, (id, filename)) in shown.iter().take(PAGE_SIZE).enumerate() {
+            let _ = writeln!(stderr, "{:>3}) {filename} ({id})", index + 1);
+        }
+        if shown.len() > PAGE_SIZE {
+            let _ = writeln!(
+                stderr,
+                "     ... {} more, type to filter",
+                shown.len() - PAGE_SIZE
+            );
+        }
+        let _ = write!(stderr, "{kind} [{filter}]> ");
+        let _ = stderr.flush();
+
+        let Some(Ok(line)) = lines.next() else {
+            return Err(ApiError::new(
+                Failure::InvalidInput,
+                format!("no {kind} picked"),
+            ));
+        };
+        let line = line.trim();
+
+        if let Ok(number) = line.parse::<usize>()
+            && let Some((id, _)) = shown
+                .get(number.wrapping_sub(1))
+                .filter(|_| number <= PAGE_SIZE)
+        {
+            return Ok(id.clone());
+        }
+
+        match (line, shown.as_slice()) {
+            ("", [(id, _)]) => return Ok(id.clone()),
+            ("", _) => filter.clear(),
+            (line, _) => filter = line.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_are_recognized() {
+        assert!(looks_like_id("ABCDEFGH12345"));
+        assert!(!looks_like_id("abcdefgh12345"));
+        assert!(!looks_like_id("S01E01"));
+        assert!(!looks_like_id("ABCDEFGH 2345"));
+    }
+}
@@ -0,0 +1,181 @@
+//! # Play Module
+//!
+//! This module plays a download with a local player, through the best
+//! transcode Real-Debrid offers for it.
+//!
+//! The transcodes of a file are tried by format, in the order of `--format`,
+//! and within a format the `full` quality is preferred, then the highest resolution.
+//! A hoster link is unrestricted first, which adds it to the downloads.
+
+use std::fs;
+use std::process::Command;
+
+use clap::ValueEnum;
+use serde_json::{Value, json};
+
+use crate::app::{Output, StreamFormat};
+use crate::error::{ApiError, Failure};
+use crate::prelude::*;
+use crate::{ARGS, downloads, hoster, pick, quota, streaming, unrestrict};
+
+/// The chosen transcode of a file.
+struct Stream {
+    id: String,
+    filename: Option<String>,
+    format: StreamFormat,
+    quality: String,
+    url: String,
+}
+
+/// Finds the best transcode of a download or hoster link, then plays it with `player`.
+///
+/// With `playlist`, an M3U playlist of the stream is written there too.
+pub fn play(
+    query: Option<String>,
+    formats: &[StreamFormat],
+    playlist: Option<&str>,
+    player: Option<&str>,
+) -> ApiResult {
+    let (id, mut filename) = match query {
+        Some(link) if link.starts_with("http://") || link.starts_with("https://") => {
+            unrestricted(link)?
+        }
+        query => (pick::download(query)?, None),
+    };
+
+    let transcodes =
+        serde_json::from_str::<Value>(&streaming::transcode(id.clone())?).unwrap_or_default();
+    let (format, quality, url) = best(&transcodes, formats).ok_or_else(|| {
+        let tried = formats
+            .iter()
+            .map(|&format| name(format))
+            .collect::<Vec<_>>();
+        ApiError::new(
+            Failure::NotFound,
+            format!("streaming : no transcode of `{id}` as {}", tried.join(", ")),
+        )
+    })?;
+    debug!("streaming : `{id}` as {} {quality}", name(format));
+
+    if let Some(path) = playlist {
+        if filename.is_none() {
+            filename = download_filename(&id);
+        }
+
+        let title = filename.as_deref().unwrap_or(&id);
+        fs::write(path, format!("#EXTM3U\n#EXTINF:-1,{title}\n{url}\n"))
+            .map_err(|e| ApiError::new(Failure::Api, format!("playlist `{path}` : {e}")))?;
+        info!("streaming : playlist written to `{path}`");
+    }
+
+    if let Some(player) = player {
+        launch(player, &url)?;
+    }
+
+    Ok(describe(&Stream {
+        id,
+        filename,
+        format,
+        quality,
+        url,
+    }))
+}
+
+/// Unrestricts a hoster link into a download, with its filename.
+fn unrestricted(link: String) -> Result<(String, Option<String>), ApiError> {
+    hoster::preflight(&link, false)?;
+    quota::preflight([link.as_str()]);
+    let download =
+        serde_json::from_str::<Value>(&unrestrict::link(link.clone())?).unwrap_or_default();
+
+    if download["streamable"] == 0 {
+        return Err(ApiError::new(
+            Failure::InvalidInput,
+            format!("streaming : `{link}` is not streamable"),
+        ));
+    }
+
+    let id = download["id"]
+        .as_str()
+        .ok_or_else(|| ApiError::new(Failure::Api, "unrestrict : no download id"))?;
+
+    Ok((
+        id.to_string(),
+        download["filename"].as_str().map(String::from),
+    ))
+}
+
+/// The filename of a download, for the playlist title.
+fn download_filename(id: &str) -> Option<String> {
+    let downloads = serde_json::from_str::<Value>(&downloads::get_all_downloads().ok()?).ok()?;
+
+    downloads
+        .as_array()?
+        .iter()
+        .find(|download| download["id"] == id)?["filename"]
+        .as_str()
+        .map(String::from)
+}
+
+/// The first format of `formats` with a transcode, with its best quality.
+pub(crate) fn best(
+    transcodes: &Value,
+    formats: &[StreamFormat],
+) -> Option<(StreamFormat, String, String)> {
+    formats.iter().find_map(|&format| {
+        let qualities = transcodes[name(format)].as_object()?;
+
+        let (quality, url) = qualities
+            .iter()
+            .filter_map(|(quality, url)| Some((quality, url.as_str()?)))
+            .max_by_key(|(quality, _)| match quality.as_str() {
+                "full" | "original" => u64::MAX,
+                quality => quality.trim_end_matches('p').parse().unwrap_or_default(),
+            })?;
+
+        Some((format, quality.clone(), url.to_string()))
+    })
+}
+
+/// Runs the player command, with the stream URL as its last argument,
+/// and waits for it to exit.
+fn launch(player: &str, url: &str) -> Result<(), ApiError> {
+    let mut words = player.split_whitespace();
+    let program = words
+        .next()
+        .ok_or_else(|| ApiError::new(Failure::InvalidInput, "player : empty command"))?;
+
+    let status = Command::new(program)
+        .args(words)
+        .arg(url)
+        .status()
+        .map_err(|e| ApiError::new(Failure::InvalidInput, format!("player `{program}` : {e}")))?;
+
+    if !status.success() {
+        warn!("player : `{program}` exited with {status}");
+    }
+
+    Ok(())
+}
+
+/// The stream URL, or a json object with `--output json`.
+fn describe(stream: &Stream) -> String {
+    match ARGS.output() {
+        Output::Text => stream.url.clone(),
+        Output::Json => json!({
+            "id": stream.id,
+            "filename": stream.filename,
+            "format": name(stream.format),
+            "quality": stream.quality,
+            "url": stream.url,
+        })
+        .to_string(),
+    }
+}
+
+fn name(format: StreamFormat) -> String {
+    format
+        .to_possible_value()
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_default()
+}
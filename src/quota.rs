@@ -0,0 +1,247 @@
+//! # Quota Module
+//!
+//! This module compares the traffic used on limited hosters with their limits.
+//!
+//! Limits are counted in links or in bytes, depending on the hoster,
+//! and extra packages raise them.
+//! `traffic check` fails when a hoster reaches a threshold, and
+//! unrestricting links first warns when their hoster is near its limit.
+
+use std::collections::HashSet;
+
+use serde_json::{Value, json};
+
+use crate::app::Output;
+use crate::error::{ApiError, Failure};
+use crate::prelude::*;
+use crate::size::humanize;
+use crate::{ARGS, hoster, traffic};
+
+/// The percentage of its limit past which unrestricting on a hoster is warned about.
+const PREFLIGHT_THRESHOLD: f64 = 80.0;
+
+const GIB: f64 = 1024.0 * 1024.0 * 1024.0;
+
+/// The usage of a limited hoster.
+struct Quota {
+    host: String,
+    /// In links, or in bytes.
+    used: f64,
+    /// In the same unit as `used`, extra packages included.
+    limit: f64,
+    links: bool,
+    /// Like `daily`.
+    reset: String,
+}
+impl Quota {
+    fn percent(&self) -> f64 {
+        self.used * 100.0 / self.limit
+    }
+
+    fn amount(&self, amount: f64) -> String {
+        match self.links {
+            true => format!("{amount} links"),
+            false => humanize(amount.max(0.0) as u64),
+        }
+    }
+
+    fn left(&self) -> String {
+        self.amount(self.limit - self.used)
+    }
+
+    fn line(&self, threshold: f64) -> String {
+        let status = match self.percent() {
+            percent if percent >= 100.0 => "over",
+            percent if percent >= threshold => "near",
+            _ => "ok",
+        };
+
+        format!(
+            "{}\t{:>5.1}%\t{} of {}\t{}\t{status}",
+            self.host,
+            self.percent(),
+            self.amount(self.used),
+            self.amount(self.limit),
+            self.reset
+        )
+    }
+
+    fn json(&self) -> Value {
+        json!({
+            "host": self.host,
+            "percent": self.percent(),
+            "used": self.used,
+            "limit": self.limit,
+            "unit": if self.links { "links" } else { "bytes" },
+            "reset": self.reset,
+        })
+    }
+}
+
+/// Reads the limited hosters of the `traffic` response.
+fn quotas(traffic: &Value) -> Vec<Quota> {
+    let mut quotas = traffic
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter_map(|(host, usage)| {
+            let limit = usage["limit"].as_f64()? + usage["extra"].as_f64().unwrap_or(0.0);
+            let (used, limit, links) = match usage["type"].as_str()? {
+                "links" => (usage["links"].as_f64()?, limit, true),
+                "gigabytes" => (usage["bytes"].as_f64()?, limit * GIB, false),
+                _ => (usage["bytes"].as_f64()?, limit, false),
+            };
+
+            // An exhausted hoster can report less usage than its limit.
+            let used = match usage["left"].as_f64() {
+                Some(left) if left <= 0.0 => used.max(limit),
+                _ => used,
+            };
+
+            (limit > 0.0).then(|| Quota {
+                host: host.clone(),
+                used,
+                limit,
+                links,
+                reset: usage["reset"].as_str().unwrap_or_default().to_string(),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    quotas.sort_by(|a, b| b.percent().total_cmp(&a.percent()));
+    quotas
+}
+
+/// Lists the limited hosters, failing when any used `threshold` percent
+/// of its limit or more.
+pub fn check(threshold: f64) -> ApiResult {
+    let traffic = serde_json::from_str::<Value>(&traffic::get_traffic()?).unwrap_or_default();
+    let quotas = quotas(&traffic);
+
+    let reached = quotas
+        .iter()
+        .filter(|quota| quota.percent() >= threshold)
+        .collect::<Vec<_>>();
+    if !reached.is_empty() {
+        let hosts = reached
+            .iter()
+            .map(|quota| format!("{} at {:.1}%", quota.host, quota.percent()))
+            .collect::<Vec<_>>();
+
+        return Err(ApiError::new(
+            Failure::HosterUnavailable,
+            format!(
+                "traffic : {} of {} limited hosters at or over {threshold}% : {}",
+                reached.len(),
+                quotas.len(),
+                hosts.join(", ")
+            ),
+        )
+        .with_body(
+            reached
+                .iter()
+                .map(|quota| quota.json())
+                .collect::<Value>()
+                .to_string(),
+        ));
+    }
+
+    Ok(match ARGS.output() {
+        Output::Text => quotas
+            .iter()
+            .map(|quota| quota.line(threshold))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Output::Json => quotas
+            .iter()
+            .map(Quota::json)
+            .collect::<Value>()
+            .to_string(),
+    })
+}
+
+/// Warns when the hoster of any of `links` is near or over its limit,
+/// once per hoster.
+///
+/// Failing to read the traffic is not worth stopping for, so it is only logged.
+pub fn preflight<'a>(links: impl IntoIterator<Item = &'a str>) {
+    let hosts = links
+        .into_iter()
+        .map(hoster::host_of)
+        .collect::<HashSet<_>>();
+    if hosts.is_empty() {
+        return;
+    }
+
+    let traffic = match traffic::get_traffic() {
+        Ok(traffic) => serde_json::from_str::<Value>(&traffic).unwrap_or_default(),
+        Err(e) => {
+            debug!("traffic : preflight skipped : {e}");
+            return;
+        }
+    };
+
+    let near = quotas(&traffic).into_iter().filter(|quota| {
+        quota.percent() >= PREFLIGHT_THRESHOLD
+            && hosts.iter().any(|host| hoster::serves(&quota.host, host))
+    });
+    for quota in near {
+        warn!(
+            "traffic : {} is at {:.1}% of its {} limit, {} left",
+            quota.host,
+            quota.percent(),
+            quota.reset,
+            quota.left()
+        );
+    }
+}
+
+/// Parses a percentage like `80%` or `80`, as a clap value parser.
+pub fn parse_percent(percent: &str) -> Result<f64, String> {
+    percent
+        .trim()
+        .trim_end_matches('%')
+        .parse::<f64>()
+        .ok()
+        .filter(|percent| (0.0..=100.0).contains(percent))
+        .ok_or_else(|| format!("invalid percentage `{percent}`, e.g. `80%`"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotas_are_sorted_by_usage() {
+        let traffic = json!({
+            "links.com": {"type": "links", "links": 2, "limit": 10, "left": 8},
+            "bytes.com": {"type": "gigabytes", "bytes": 1_073_741_824u64, "limit": 1, "extra": 1, "left": 1},
+            "free.com": {"type": "links", "links": 0, "limit": 0, "left": 0},
+        });
+
+        let quotas = quotas(&traffic);
+
+        assert_eq!(quotas.len(), 2);
+        assert_eq!(quotas[0].host, "bytes.com");
+        assert_eq!(quotas[0].limit, 2.0 * GIB);
+        assert_eq!(quotas[0].percent(), 50.0);
+        assert_eq!(quotas[1].host, "links.com");
+        assert!(quotas[1].links);
+    }
+
+    #[test]
+    fn exhausted_quotas_are_full() {
+        let traffic = json!({
+            "host.com": {"type": "bytes", "bytes": 10, "limit": 100, "left": 0},
+        });
+
+        assert_eq!(quotas(&traffic)[0].percent(), 100.0);
+    }
+
+    #[test]
+    fn percentages_are_parsed() {
+        assert_eq!(parse_percent("80%"), Ok(80.0));
+        assert_eq!(parse_percent(" 95 "), Ok(95.0));
+        assert!(parse_percent("120%").is_err());
+    }
+}
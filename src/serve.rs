@@ -0,0 +1,330 @@
+//! # Serve Module
+//!
+//! This module runs local HTTP servers backed by Real-Debrid.
+//!
+//! - By default, a REST proxy of the library functions, see `proxy`.
+//! - `--qbittorrent` emulates the qBittorrent WebUI API, see `qbittorrent`.
+//! - `--webdav` serves downloaded torrents as a file tree, see `webdav`.
+//!
+//! This module holds the HTTP plumbing shared by the servers:
+//! handling requests with a fixed pool of `--workers` threads,
+//! reading query strings and form bodies, and replying.
+
+use std::thread;
+
+use tiny_http::{Header, Request, Response, Server};
+
+use crate::error::{ApiError, Failure};
+
+mod proxy;
+mod qbittorrent;
+mod webdav;
+
+pub use proxy::run as run_proxy;
+pub use qbittorrent::run as run_qbittorrent;
+pub use webdav::run as run_webdav;
+
+/// A field of a query string or form body.
+pub(crate) struct Field {
+    pub(crate) name: String,
+    /// The uploaded file name, for multipart file fields.
+    pub(crate) filename: Option<String>,
+    pub(crate) value: Vec<u8>,
+}
+impl Field {
+    pub(crate) fn text(&self) -> String {
+        String::from_utf8_lossy(&self.value).into_owned()
+    }
+}
+
+/// Starts listening on `listen`, e.g. `127.0.0.1:8080`.
+pub(crate) fn listen(listen: &str) -> Result<Server, ApiError> {
+    let server = Server::http(listen)
+        .map_err(|e| ApiError::new(Failure::InvalidInput, format!("serve : `{listen}` : {e}")))?;
+
+    info!("serve : listening on http://{listen}");
+
+    Ok(server)
+}
+
+/// How many requests are handled at once by default.
+///
+/// A burst cannot spawn a thread per request, but every streamed file
+/// holds a worker until it is read, so `--workers` should exceed the streams.
+pub const WORKERS: usize = 16;
+
+/// Handles the requests of `server` with `workers` threads until it stops.
+pub(crate) fn serve(server: &Server, workers: usize, handle: impl Fn(Request) + Sync) {
+    thread::scope(|scope| {
+        for _ in 0..workers.max(1) {
+            scope.spawn(|| server.incoming_requests().for_each(&handle));
+        }
+    });
+}
+
+/// Decodes `%XX` escapes, like those of a url path.
+pub(crate) fn percent_decode(text: &str) -> String {
+    decode(text, false)
+}
+
+/// Decodes `%XX` escapes and `+` as a space, like those of a urlencoded form.
+fn form_decode(text: &str) -> String {
+    decode(text, true)
+}
+
+fn decode(text: &str, plus_as_space: bool) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' if plus_as_space => decoded.push(b' '),
+            b'%' => {
+                let escaped = bytes
+                    .get(i + 1..i + 3)
+                    .and_then(|hex| std::str::from_utf8(hex).ok())
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                match escaped {
+                    Some(byte) => {
+                        decoded.push(byte);
+                        i += 2;
+                    }
+                    None => decoded.push(b'%'),
+                }
+            }
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Escapes every byte of a path segment but unreserved characters.
+pub(crate) fn percent_encode(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                char::from(byte).to_string()
+            }
+            byte => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+/// Splits a request url into its path and its query fields.
+pub(crate) fn split_url(url: &str) -> (String, Vec<Field>) {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+
+    (percent_decode(path), urlencoded(query))
+}
+
+/// Parses an `application/x-www-form-urlencoded` string.
+fn urlencoded(text: &str) -> Vec<Field> {
+    text.split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            Field {
+                name: form_decode(name),
+                filename: None,
+                value: form_decode(value).into_bytes(),
+            }
+        })
+        .collect()
+}
+
+/// The value of a request header.
+pub(crate) fn header(request: &Request, name: &str) -> Option<String> {
+    request
+        .headers()
+        .iter()
+        .find(|header| header.field.as_str().as_str().eq_ignore_ascii_case(name))
+        .map(|header| header.value.to_string())
+}
+
+/// Reads the body of a request.
+pub(crate) fn body(request: &mut Request) -> Vec<u8> {
+    let mut body = Vec::new();
+    if let Err(e) = request.as_reader().read_to_end(&mut body) {
+        warn!("serve : reading request body : {e}");
+    }
+
+    body
+}
+
+/// Reads a urlencoded or multipart form body.
+pub(crate) fn form(request: &mut Request) -> Vec<Field> {
+    let content_type = header(request, "Content-Type").unwrap_or_default();
+
+    parse_form(&content_type, &body(request))
+}
+
+/// Parses a urlencoded or multipart form body of the given content type.
+pub(crate) fn parse_form(content_type: &str, body: &[u8]) -> Vec<Field> {
+    match content_type
+        .split(';')
+        .find_map(|parameter| parameter.trim().strip_prefix("boundary="))
+    {
+        Some(boundary) => multipart(body, boundary.trim_matches('"')),
+        None => urlencoded(&String::from_utf8_lossy(body)),
+    }
+}
+
+/// Parses a `multipart/form-data` body.
+fn multipart(body: &[u8], boundary: &str) -> Vec<Field> {
+    let delimiter = format!("--{boundary}").into_bytes();
+    let mut fields = Vec::new();
+
+    for part in split_bytes(body, &delimiter).skip(1) {
+        // The closing delimiter is followed by `--`.
+        if part.starts_with(b"--") {
+            break;
+        }
+
+        let part = part.strip_prefix(b"\r\n").unwrap_or(part);
+        let Some(headers_end) = part.windows(4).position(|window| window == b"\r\n\r\n") else {
+            continue;
+        };
+
+        let headers = String::from_utf8_lossy(&part[..headers_end]);
+        let value = &part[headers_end + 4..];
+        let value = value.strip_suffix(b"\r\n").unwrap_or(value);
+
+        let disposition = headers
+            .lines()
+            .find(|line| {
+                line.to_ascii_lowercase()
+                    .starts_with("content-disposition:")
+            })
+            .unwrap_or_default();
+        let parameter = |key: &str| {
+            disposition.split(';').find_map(|parameter| {
+                parameter
+                    .trim()
+                    .strip_prefix(key)
+                    .and_then(|value| value.strip_prefix('='))
+                    .map(|value| value.trim_matches('"').to_string())
+            })
+        };
+
+        if let Some(name) = parameter("name") {
+            fields.push(Field {
+                name,
+                filename: parameter("filename"),
+                value: value.to_vec(),
+            });
+        }
+    }
+
+    fields
+}
+
+/// Splits `bytes` on every occurrence of `delimiter`.
+fn split_bytes<'a>(bytes: &'a [u8], delimiter: &'a [u8]) -> impl Iterator<Item = &'a [u8]> {
+    let mut rest = Some(bytes);
+
+    std::iter::from_fn(move || {
+        let current = rest?;
+        match current
+            .windows(delimiter.len())
+            .position(|window| window == delimiter)
+        {
+            Some(at) => {
+                rest = Some(&current[at + delimiter.len()..]);
+                Some(&current[..at])
+            }
+            None => {
+                rest = None;
+                Some(current)
+            }
+        }
+    })
+}
+
+/// The first field named `name`, as text.
+pub(crate) fn field(fields: &[Field], name: &str) -> Option<String> {
+    fields
+        .iter()
+        .find(|field| field.name == name)
+        .map(Field::text)
+}
+
+/// Replies to a request, logging failures to reply.
+pub(crate) fn reply(
+    request: Request,
+    status: u16,
+    content_type: &str,
+    body: impl Into<Vec<u8>>,
+    headers: &[(&str, String)],
+) {
+    let method = request.method().to_string();
+    let url = request.url().to_string();
+
+    let mut response = Response::from_data(body.into()).with_status_code(status);
+    for (name, value) in [("Content-Type", content_type.to_string())]
+        .iter()
+        .chain(headers)
+    {
+        if let Ok(header) = Header::from_bytes(name.as_bytes(), value.as_bytes()) {
+            response.add_header(header);
+        }
+    }
+
+    debug!("serve : {method} {url} : {status}");
+    if let Err(e) = request.respond(response) {
+        warn!("serve : {method} {url} : {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_keep_plus_signs() {
+        let (path, _) = split_url("/torrents/C++%20Primer/a%2Bb.pdf");
+
+        assert_eq!(path, "/torrents/C++ Primer/a+b.pdf");
+    }
+
+    #[test]
+    fn queries_decode_plus_as_space() {
+        let (_, fields) = split_url("/search?name=C%2B%2B+Primer&empty");
+
+        assert_eq!(fields[0].name, "name");
+        assert_eq!(fields[0].text(), "C++ Primer");
+        assert_eq!(fields[1].name, "empty");
+        assert_eq!(fields[1].text(), "");
+    }
+
+    #[test]
+    fn multipart_fields_are_parsed() {
+        let body = b"--xyz\r\n\
+Content-Disposition: form-data; name=\"category\"\r\n\r\n\
+tv\r\n\
+--xyz\r\n\
+Content-Disposition: form-data; name=\"torrents\"; filename=\"a.torrent\"\r\n\
+Content-Type: application/x-bittorrent\r\n\r\n\
+d4:infoe\r\n\
+--xyz--\r\n";
+
+        let fields = parse_form("multipart/form-data; boundary=\"xyz\"", body);
+
+        assert_eq!(fields.len(), 2);
+        assert_eq!(fields[0].name, "category");
+        assert_eq!(fields[0].filename, None);
+        assert_eq!(fields[0].text(), "tv");
+        assert_eq!(fields[1].name, "torrents");
+        assert_eq!(fields[1].filename.as_deref(), Some("a.torrent"));
+        assert_eq!(fields[1].value, b"d4:infoe");
+    }
+
+    #[test]
+    fn bad_escapes_are_kept() {
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz%C3%A9"), "%zzé");
+    }
+}
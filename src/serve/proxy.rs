@@ -0,0 +1,740 @@
+//! # Proxy Module
+//!
+//! This module serves the library functions as a local REST/JSON API,
+//! so other tools can use Real-Debrid without holding the API key.
+//!
+//! Routes mirror the Real-Debrid API paths, e.g. `GET /torrents/info/{id}`
+//! or `POST /unrestrict/link` with a `link` form field,
+//! and answer with the raw Real-Debrid JSON.
+//! `GET /openapi.json` describes every route.
+//!
+//! - Clients authenticate with `Authorization: Bearer <token>`,
+//!   where the tokens are listed in `--token-file`, one per line.
+//! - `GET` responses are cached for `--cache-ttl` seconds,
+//!   and the cache is cleared by any successful change.
+//! - Calls to Real-Debrid are limited to `--rate-limit` per minute,
+//!   shared by every client. Over the limit, clients get a `429`.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde_json::{Map, Value, json};
+use tiny_http::{Method, Request};
+
+use super::{Field, body, header, parse_form, reply, split_url};
+use crate::app::ProxyArgs;
+use crate::error::{ApiError, Failure};
+use crate::{
+    ApiResult, date, downloads, hosts, settings, streaming, torrents, traffic, unrestrict, user,
+};
+
+const JSON: &str = "application/json";
+
+/// Where a parameter is read from.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Location {
+    /// A `{name}` segment of the path
+    Path,
+    Query,
+    Form,
+}
+
+struct Parameter {
+    name: &'static str,
+    location: Location,
+    required: bool,
+}
+
+const fn path(name: &'static str) -> Parameter {
+    Parameter {
+        name,
+        location: Location::Path,
+        required: true,
+    }
+}
+
+const fn form(name: &'static str) -> Parameter {
+    Parameter {
+        name,
+        location: Location::Form,
+        required: true,
+    }
+}
+
+/// The parameters of a call.
+struct Arguments {
+    values: HashMap<String, String>,
+    /// The raw request body.
+    body: Vec<u8>,
+}
+impl Arguments {
+    fn get(&self, name: &str) -> Result<String, ApiError> {
+        self.values.get(name).cloned().ok_or_else(|| {
+            ApiError::new(
+                Failure::InvalidInput,
+                format!("serve : missing parameter `{name}`"),
+            )
+        })
+    }
+}
+
+/// A proxied library function.
+struct Route {
+    method: &'static str,
+    /// The path, with `{name}` segments for path parameters.
+    path: &'static str,
+    summary: &'static str,
+    parameters: &'static [Parameter],
+    /// Whether the request body is a raw file rather than a form.
+    upload: Option<&'static str>,
+    call: fn(&Arguments) -> ApiResult,
+}
+
+const ROUTES: &[Route] = &[
+    Route {
+        method: "GET",
+        path: "/user",
+        summary: "Get information on the current user",
+        parameters: &[],
+        upload: None,
+        call: |_| user::get_user(),
+    },
+    Route {
+        method: "POST",
+        path: "/unrestrict/check",
+        summary: "Check if a file is downloadable on the concerned hoster",
+        parameters: &[form("link")],
+        upload: None,
+        call: |arguments| unrestrict::check(arguments.get("link")?),
+    },
+    Route {
+        method: "POST",
+        path: "/unrestrict/link",
+        summary: "Unrestrict a hoster link, protected by `password`, using remote traffic with `remote=1`",
+        parameters: &[
+            form("link"),
+            Parameter {
+                name: "password",
+                location: Location::Form,
+                required: false,
+            },
+            Parameter {
+                name: "remote",
+                location: Location::Form,
+                required: false,
+            },
+        ],
+        upload: None,
+        call: |arguments| {
+            unrestrict::link_with(
+                arguments.get("link")?,
+                arguments.get("password").ok().as_deref(),
+                arguments.get("remote").is_ok_and(|remote| remote == "1"),
+            )
+        },
+    },
+    Route {
+        method: "POST",
+        path: "/unrestrict/folder",
+        summary: "Unrestrict a hoster folder link into individual links",
+        parameters: &[form("link")],
+        upload: None,
+        call: |arguments| unrestrict::folder(arguments.get("link")?),
+    },
+    Route {
+        method: "POST",
+        path: "/unrestrict/containerLink",
+        summary: "Decrypt a container file from a link",
+        parameters: &[form("link")],
+        upload: None,
+        call: |arguments| unrestrict::container_link(arguments.get("link")?),
+    },
+    Route {
+        method: "GET",
+        path: "/traffic",
+        summary: "Get traffic information for limited hosters",
+        parameters: &[],
+        upload: None,
+        call: |_| traffic::get_traffic(),
+    },
+    Route {
+        method: "GET",
+        path: "/traffic/details",
+        summary: "Get traffic details on each hoster",
+        parameters: &[
+            Parameter {
+                name: "start",
+                location: Location::Query,
+                required: false,
+            },
+            Parameter {
+                name: "end",
+                location: Location::Query,
+                required: false,
+            },
+        ],
+        upload: None,
+        call: |arguments| {
+            // Checked, as they are pasted into the query string.
+            let day = |name| {
+                arguments
+                    .get(name)
+                    .ok()
+                    .map(|day| {
+                        date::parse_day(&day).map_err(|e| {
+                            ApiError::new(Failure::InvalidInput, format!("serve : `{name}` : {e}"))
+                        })
+                    })
+                    .transpose()
+            };
+            traffic::get_details(day("start")?, day("end")?)
+        },
+    },
+    Route {
+        method: "GET",
+        path: "/streaming/transcode/{id}",
+        summary: "Get transcoding links for a download",
+        parameters: &[path("id")],
+        upload: None,
+        call: |arguments| streaming::transcode(arguments.get("id")?),
+    },
+    Route {
+        method: "GET",
+        path: "/streaming/mediaInfos/{id}",
+        summary: "Get media information for a download",
+        parameters: &[path("id")],
+        upload: None,
+        call: |arguments| streaming::media_infos(arguments.get("id")?),
+    },
+    Route {
+        method: "GET",
+        path: "/downloads",
+        summary: "Get every download",
+        parameters: &[],
+        upload: None,
+        call: |_| downloads::get_all_downloads(),
+    },
+    Route {
+        method: "DELETE",
+        path: "/downloads/delete/{id}",
+        summary: "Delete a download",
+        parameters: &[path("id")],
+        upload: None,
+        call: |arguments| downloads::delete_download(arguments.get("id")?),
+    },
+    Route {
+        method: "GET",
+        path: "/torrents",
+        summary: "Get every torrent",
+        parameters: &[],
+        upload: None,
+        call: |_| torrents::get_all_torrents(),
+    },
+    Route {
+        method: "GET",
+        path: "/torrents/info/{id}",
+        summary: "Get all information on a torrent",
+        parameters: &[path("id")],
+        upload: None,
+        call: |arguments| torrents::get_torrent_info(arguments.get("id")?),
+    },
+    Route {
+        method: "GET",
+        path: "/torrents/activeCount",
+        summary: "Get the number of active torrents and the limit",
+        parameters: &[],
+        upload: None,
+        call: |_| torrents::get_active_count(),
+    },
+    Route {
+        method: "GET",
+        path: "/torrents/availableHosts",
+        summary: "Get the hosts torrents can be uploaded to",
+        parameters: &[],
+        upload: None,
+        call: |_| torrents::get_available_hosts(),
+    },
+    Route {
+        method: "PUT",
+        path: "/torrents/addTorrent",
+        summary: "Add a torrent file, sent as the request body",
+        parameters: &[Parameter {
+            name: "host",
+            location: Location::Query,
+            required: false,
+        }],
+        upload: Some("application/x-bittorrent"),
+        call: |arguments| torrents::add_torrent(arguments.body.clone(), arguments.get("host").ok()),
+    },
+    Route {
+        method: "POST",
+        path: "/torrents/addMagnet",
+        summary: "Add a magnet link",
+        parameters: &[form("magnet")],
+        upload: None,
+        call: |arguments| torrents::add_magnet(arguments.get("magnet")?),
+    },
+    Route {
+        method: "POST",
+        path: "/torrents/selectFiles/{id}",
+        summary: "Select the files of a torrent, comma separated ids or `all`",
+        parameters: &[path("id"), form("files")],
+        upload: None,
+        call: |arguments| torrents::select_files(arguments.get("id")?, arguments.get("files")?),
+    },
+    Route {
+        method: "DELETE",
+        path: "/torrents/delete/{id}",
+        summary: "Delete a torrent",
+        parameters: &[path("id")],
+        upload: None,
+        call: |arguments| torrents::delete(arguments.get("id")?),
+    },
+    Route {
+        method: "GET",
+        path: "/hosts",
+        summary: "Get the supported hosts",
+        parameters: &[],
+        upload: None,
+        call: |_| hosts::get_hosts(),
+    },
+    Route {
+        method: "GET",
+        path: "/hosts/status",
+        summary: "Get the status of the hosters",
+        parameters: &[],
+        upload: None,
+        call: |_| hosts::get_status(),
+    },
+    Route {
+        method: "GET",
+        path: "/hosts/regex",
+        summary: "Get the regexes of supported links",
+        parameters: &[],
+        upload: None,
+        call: |_| hosts::get_regex(),
+    },
+    Route {
+        method: "GET",
+        path: "/hosts/regexFolder",
+        summary: "Get the regexes of supported folder links",
+        parameters: &[],
+        upload: None,
+        call: |_| hosts::get_regex_folder(),
+    },
+    Route {
+        method: "GET",
+        path: "/hosts/domains",
+        summary: "Get the supported hoster domains",
+        parameters: &[],
+        upload: None,
+        call: |_| hosts::get_domains(),
+    },
+    Route {
+        method: "GET",
+        path: "/settings",
+        summary: "Get the user settings",
+        parameters: &[],
+        upload: None,
+        call: |_| settings::get_settings(),
+    },
+    Route {
+        method: "POST",
+        path: "/settings/update",
+        summary: "Update a user setting",
+        parameters: &[form("setting_name"), form("setting_value")],
+        upload: None,
+        call: |arguments| {
+            settings::update(
+                arguments.get("setting_name")?,
+                arguments.get("setting_value")?,
+            )
+        },
+    },
+    Route {
+        method: "POST",
+        path: "/settings/convertPoints",
+        summary: "Convert fidelity points",
+        parameters: &[],
+        upload: None,
+        call: |_| settings::convert_points(),
+    },
+];
+
+/// A cached `GET` response.
+struct Cached {
+    at: Instant,
+    body: String,
+}
+
+/// A token bucket shared by every client.
+struct RateLimit {
+    per_minute: u32,
+    tokens: f64,
+    refilled: Instant,
+}
+impl RateLimit {
+    /// Takes a token, or tells how long until one is available.
+    fn take(&mut self) -> Result<(), Duration> {
+        let capacity = f64::from(self.per_minute);
+        let per_second = capacity / 60.0;
+
+        self.tokens =
+            (self.tokens + self.refilled.elapsed().as_secs_f64() * per_second).min(capacity);
+        self.refilled = Instant::now();
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / per_second))
+        }
+    }
+}
+
+struct Proxy {
+    tokens: HashSet<String>,
+    cache_ttl: Duration,
+    cache: Mutex<HashMap<String, Cached>>,
+    rate_limit: Mutex<RateLimit>,
+}
+
+/// Serves the REST proxy on `listen` until the server stops.
+pub fn run(listen: &str, workers: usize, args: &ProxyArgs) -> Result<(), ApiError> {
+    let Some(token_file) = args.token_file() else {
+        return Err(ApiError::new(
+            Failure::InvalidInput,
+            "serve : the proxy requires client tokens, see `--token-file`",
+        ));
+    };
+
+    let tokens = fs::read_to_string(token_file)
+        .map_err(|e| {
+            ApiError::new(
+                Failure::InvalidInput,
+                format!("serve : `{token_file}` : {e}"),
+            )
+        })?
+        .lines()
+        .map(str::trim)
+        .filter(|token| !token.is_empty() && !token.starts_with('#'))
+        .map(String::from)
+        .collect::<HashSet<_>>();
+
+    if tokens.is_empty() {
+        return Err(ApiError::new(
+            Failure::InvalidInput,
+            format!("serve : `{token_file}` : no tokens"),
+        ));
+    }
+
+    let proxy = Proxy {
+        tokens,
+        cache_ttl: Duration::from_secs(*args.cache_ttl()),
+        cache: Mutex::new(HashMap::new()),
+        rate_limit: Mutex::new(RateLimit {
+            per_minute: (*args.rate_limit()).max(1),
+            tokens: f64::from((*args.rate_limit()).max(1)),
+            refilled: Instant::now(),
+        }),
+    };
+
+    let http = super::listen(listen)?;
+    super::serve(&http, workers, |request| proxy.handle(request));
+
+    Ok(())
+}
+
+impl Proxy {
+    fn handle(&self, mut request: Request) {
+        let url = request.url().to_string();
+        let (path, query) = split_url(&url);
+        let method = request.method().to_string();
+
+        if path == "/openapi.json" && *request.method() == Method::Get {
+            return reply(request, 200, JSON, openapi().to_string(), &[]);
+        }
+
+        if !self.authorized(&request) {
+            return reply(
+                request,
+                401,
+                JSON,
+                error_body("missing or unknown bearer token"),
+                &[("WWW-Authenticate", String::from("Bearer"))],
+            );
+        }
+
+        let Some((route, mut values)) = ROUTES
+            .iter()
+            .find_map(|route| Some((route, matches(route, &method, &path)?)))
+        else {
+            return reply(request, 404, JSON, error_body("no such route"), &[]);
+        };
+
+        let cacheable = method == "GET" && !self.cache_ttl.is_zero();
+        if cacheable && let Some(body) = self.cached(&url) {
+            return reply(
+                request,
+                200,
+                JSON,
+                body,
+                &[("X-Cache", String::from("hit"))],
+            );
+        }
+
+        let body = body(&mut request);
+        let fields = match route.upload {
+            Some(_) => Vec::new(),
+            None => parse_form(&header(&request, "Content-Type").unwrap_or_default(), &body),
+        };
+        for Field { name, value, .. } in query.into_iter().chain(fields) {
+            values
+                .entry(name)
+                .or_insert_with(|| String::from_utf8_lossy(&value).into_owned());
+        }
+
+        if let Some(missing) = route
+            .parameters
+            .iter()
+            .find(|parameter| parameter.required && !values.contains_key(parameter.name))
+        {
+            let message = format!("missing parameter `{}`", missing.name);
+            return reply(request, 400, JSON, error_body(&message), &[]);
+        }
+
+        if let Err(wait) = self
+            .rate_limit
+            .lock()
+            .map(|mut limit| limit.take())
+            .unwrap_or(Ok(()))
+        {
+            let retry_after = wait.as_secs() + 1;
+            return reply(
+                request,
+                429,
+                JSON,
+                error_body("rate limited"),
+                &[("Retry-After", retry_after.to_string())],
+            );
+        }
+
+        match (route.call)(&Arguments { values, body }) {
+            Ok(body) => {
+                if cacheable {
+                    self.store(&url, &body);
+                } else if method != "GET" {
+                    self.clear_cache();
+                }
+
+                let status = if body.is_empty() { 204 } else { 200 };
+                reply(request, status, JSON, body, &[]);
+            }
+            Err(e) => {
+                warn!("serve : {method} {path} : {e}");
+                let body = if e.body().is_empty() {
+                    error_body(e.message())
+                } else {
+                    e.body().clone()
+                };
+                reply(request, status_of(&e), JSON, body, &[]);
+            }
+        }
+    }
+
+    fn authorized(&self, request: &Request) -> bool {
+        header(request, "Authorization")
+            .as_deref()
+            .and_then(|authorization| authorization.strip_prefix("Bearer "))
+            .is_some_and(|token| self.tokens.contains(token.trim()))
+    }
+
+    fn cached(&self, url: &str) -> Option<String> {
+        let cache = self.cache.lock().ok()?;
+        cache
+            .get(url)
+            .filter(|cached| cached.at.elapsed() < self.cache_ttl)
+            .map(|cached| cached.body.clone())
+    }
+
+    fn store(&self, url: &str, body: &str) {
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.retain(|_, cached| cached.at.elapsed() < self.cache_ttl);
+            cache.insert(
+                url.to_string(),
+                Cached {
+                    at: Instant::now(),
+                    body: body.to_string(),
+                },
+            );
+        }
+    }
+
+    fn clear_cache(&self) {
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.clear();
+        }
+    }
+}
+
+/// The path parameters of a request matching `route`.
+fn matches(route: &Route, method: &str, path: &str) -> Option<HashMap<String, String>> {
+    if route.method != method {
+        return None;
+    }
+
+    let pattern = route.path.trim_matches('/').split('/').collect::<Vec<_>>();
+    let segments = path.trim_matches('/').split('/').collect::<Vec<_>>();
+    if pattern.len() != segments.len() {
+        return None;
+    }
+
+    let mut values = HashMap::new();
+    for (expected, segment) in pattern.iter().zip(segments) {
+        match expected
+            .strip_prefix('{')
+            .and_then(|name| name.strip_suffix('}'))
+        {
+            Some(name) if !segment.is_empty() => {
+                values.insert(name.to_string(), segment.to_string());
+            }
+            None if *expected == segment => (),
+            _ => return None,
+        }
+    }
+
+    Some(values)
+}
+
+/// The HTTP status for a failed call.
+fn status_of(e: &ApiError) -> u16 {
+    if let Some(status) = *e.status() {
+        return status;
+    }
+
+    match e.failure() {
+        Failure::InvalidInput => 400,
+        Failure::Auth => 401,
+        Failure::NotFound => 404,
+        Failure::RateLimited => 429,
+        Failure::HosterUnavailable => 503,
+        Failure::Expiring => 402,
+        Failure::Api | Failure::Network => 502,
+        Failure::Io => 500,
+    }
+}
+
+fn error_body(message: &str) -> String {
+    json!({ "error": message }).to_string()
+}
+
+/// A camel case name for a route, e.g. `getTorrentsInfo`.
+fn operation_id(route: &Route) -> String {
+    let mut id = route.method.to_lowercase();
+    for segment in route
+        .path
+        .split('/')
+        .filter(|segment| !segment.starts_with('{'))
+    {
+        let mut chars = segment.chars();
+        if let Some(first) = chars.next() {
+            id.push(first.to_ascii_uppercase());
+            id.extend(chars);
+        }
+    }
+
+    id
+}
+
+/// The OpenAPI 3.0 description of every route.
+fn openapi() -> Value {
+    let mut paths = Map::new();
+
+    for route in ROUTES {
+        let parameters = route
+            .parameters
+            .iter()
+            .filter(|parameter| parameter.location != Location::Form)
+            .map(|parameter| {
+                json!({
+                    "name": parameter.name,
+                    "in": if parameter.location == Location::Path { "path" } else { "query" },
+                    "required": parameter.required,
+                    "schema": { "type": "string" },
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let form_fields = route
+            .parameters
+            .iter()
+            .filter(|parameter| parameter.location == Location::Form)
+            .collect::<Vec<_>>();
+
+        let request_body = match route.upload {
+            Some(content_type) => Some(json!({
+                "required": true,
+                "content": { content_type: { "schema": { "type": "string", "format": "binary" } } },
+            })),
+            None if !form_fields.is_empty() => Some(json!({
+                "required": true,
+                "content": {
+                    "application/x-www-form-urlencoded": {
+                        "schema": {
+                            "type": "object",
+                            "properties": form_fields
+                                .iter()
+                                .map(|parameter| (parameter.name.to_string(), json!({ "type": "string" })))
+                                .collect::<Map<_, _>>(),
+                            "required": form_fields
+                                .iter()
+                                .filter(|parameter| parameter.required)
+                                .map(|parameter| parameter.name)
+                                .collect::<Vec<_>>(),
+                        },
+                    },
+                },
+            })),
+            None => None,
+        };
+
+        let mut operation = json!({
+            "summary": route.summary,
+            "operationId": operation_id(route),
+            "parameters": parameters,
+            "responses": {
+                "200": {
+                    "description": "The Real-Debrid response",
+                    "content": { JSON: { "schema": {} } },
+                },
+                "204": { "description": "Done, without content" },
+                "401": { "description": "Missing or unknown bearer token" },
+                "429": { "description": "Rate limited, see `Retry-After`" },
+            },
+        });
+        if let Some(request_body) = request_body {
+            operation["requestBody"] = request_body;
+        }
+
+        let methods = paths.entry(route.path).or_insert_with(|| json!({}));
+        methods[route.method.to_lowercase()] = operation;
+    }
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "traffic_cone",
+            "description": "Real-Debrid proxied by traffic_cone",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "components": {
+            "securitySchemes": {
+                "bearer": { "type": "http", "scheme": "bearer" },
+            },
+        },
+        "security": [{ "bearer": [] }],
+        "paths": paths,
+    })
+}
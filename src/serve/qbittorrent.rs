@@ -0,0 +1,691 @@
+//! # qBittorrent Module
+//!
+//! This module emulates enough of the qBittorrent WebUI API (v2)
+//! for Sonarr and Radarr to use Real-Debrid as their torrent client.
+//!
+//! Every added torrent becomes a job, see the `jobs` module, which downloads
+//! into the folder of its category, `<download-dir>/<category>`.
+//! A worker thread advances unfinished jobs, and a torrent is reported
+//! complete once its files are downloaded locally.
+//! The download on Real-Debrid and the local download each make up
+//! half of the reported progress.
+//!
+//! The Real-Debrid torrents listing is reused for one worker interval,
+//! as clients poll `torrents/info` every few seconds.
+//! Jobs without a known info hash, like failed ones, get a stable
+//! hash made from their job id.
+//!
+//! Supported endpoints, under `/api/v2/`:
+//! - `auth/login`, `auth/logout`
+//! - `app/version`, `app/webapiVersion`, `app/buildInfo`, `app/preferences`
+//! - `torrents/info`, `torrents/properties`, `torrents/files`
+//! - `torrents/add`, `torrents/delete`
+//! - `torrents/categories`, `torrents/createCategory`, `torrents/setCategory`
+//!
+//! Pausing, priorities, share limits and preference changes are accepted and ignored.
+
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use reqwest::blocking::Client as ReqwestClient;
+use reqwest::header::LOCATION;
+use reqwest::redirect::Policy;
+use serde_json::{Value, json};
+use tiny_http::{Method, Request};
+
+use super::{Field, field, form, header, reply, split_url};
+use crate::app::{JobState, QbittorrentArgs, SelectPolicy};
+use crate::date;
+use crate::error::{ApiError, Failure};
+use crate::fetch::sanitize_filename;
+use crate::jobs::{self, Job, Progress, Source, Store};
+use crate::{read_api_key, torrents};
+
+const VERSION: &str = "v4.6.7";
+const WEB_API_VERSION: &str = "2.9.3";
+
+/// The `eta` of a torrent with no estimate, as qBittorrent reports it.
+const ETA_UNKNOWN: u64 = 8_640_000;
+
+/// How many redirects are followed when fetching a `.torrent` url.
+const MAX_REDIRECTS: usize = 5;
+
+const TEXT: &str = "text/plain; charset=UTF-8";
+const JSON: &str = "application/json";
+
+/// Endpoints which are accepted without doing anything.
+const IGNORED_ENDPOINTS: [&str; 13] = [
+    "app/setPreferences",
+    "torrents/pause",
+    "torrents/resume",
+    "torrents/stop",
+    "torrents/start",
+    "torrents/setShareLimits",
+    "torrents/topPrio",
+    "torrents/bottomPrio",
+    "torrents/setForceStart",
+    "torrents/recheck",
+    "torrents/reannounce",
+    "torrents/editCategory",
+    "torrents/removeCategories",
+];
+
+/// Fetches `.torrent` urls, stopping at redirects which may lead to magnet links.
+static TORRENT_CLIENT: LazyLock<ReqwestClient> = LazyLock::new(|| {
+    ReqwestClient::builder()
+        .redirect(Policy::none())
+        .build()
+        .unwrap_or_default()
+});
+
+/// A successful reply.
+enum Reply {
+    Text(&'static str),
+    Json(Value),
+}
+
+struct Server {
+    store: Mutex<Store>,
+    download_dir: PathBuf,
+    select: SelectPolicy,
+    username: String,
+    password: Option<String>,
+    sessions: Mutex<HashSet<String>>,
+    /// How long the torrents listing is reused for.
+    interval: Duration,
+    listing: Mutex<Option<(Instant, Value)>>,
+}
+
+/// Serves the qBittorrent WebUI API on `listen` until the server stops.
+pub fn run(listen: &str, workers: usize, args: &QbittorrentArgs) -> Result<(), ApiError> {
+    let setup_error = |e: io::Error, path: &str| {
+        ApiError::new(
+            Failure::InvalidInput,
+            format!("qbittorrent : `{path}` : {e}"),
+        )
+    };
+
+    let download_dir = PathBuf::from(args.download_dir());
+    fs::create_dir_all(&download_dir).map_err(|e| setup_error(e, args.download_dir()))?;
+
+    let password = match args.password_file() {
+        Some(path) => Some(read_api_key(path).map_err(|e| setup_error(e, path))?),
+        None => None,
+    };
+
+    let interval = Duration::from_secs((*args.interval()).max(1));
+    let server = Server {
+        store: Mutex::new(Store::open()?),
+        download_dir,
+        select: *args.select(),
+        username: args.username().clone(),
+        password,
+        sessions: Mutex::default(),
+        interval,
+        listing: Mutex::new(None),
+    };
+
+    let http = super::listen(listen)?;
+
+    thread::spawn(move || work(interval));
+
+    super::serve(&http, workers, |request| server.handle(request));
+
+    Ok(())
+}
+
+/// Advances unfinished jobs forever.
+fn work(interval: Duration) {
+    let store = match Store::open() {
+        Ok(store) => store,
+        Err(e) => {
+            error!("qbittorrent : {e}");
+            return;
+        }
+    };
+
+    loop {
+        match store.unfinished() {
+            Ok(unfinished) => {
+                for mut job in unfinished {
+                    if let Progress::Failed(reason) = jobs::advance(&store, &mut job) {
+                        error!("qbittorrent : `{}` : {reason}", job.name());
+                    }
+                }
+            }
+            Err(e) => warn!("qbittorrent : {e}"),
+        }
+
+        thread::sleep(interval);
+    }
+}
+
+impl Server {
+    fn handle(&self, mut request: Request) {
+        let (path, mut fields) = split_url(request.url());
+        let Some(endpoint) = path.strip_prefix("/api/v2/").map(String::from) else {
+            return reply(request, 404, TEXT, "Not Found", &[]);
+        };
+
+        if *request.method() == Method::Post {
+            fields.extend(form(&mut request));
+        }
+
+        if endpoint == "auth/login" {
+            return self.login(request, &fields);
+        }
+        if !self.authorized(&request) {
+            return reply(request, 403, TEXT, "Forbidden", &[]);
+        }
+
+        let outcome = match endpoint.as_str() {
+            "auth/logout" => {
+                if let Some(session) = session(&request) {
+                    self.sessions().remove(&session);
+                }
+                Ok(Reply::Text(""))
+            }
+            "app/version" => Ok(Reply::Text(VERSION)),
+            "app/webapiVersion" => Ok(Reply::Text(WEB_API_VERSION)),
+            "app/buildInfo" => Ok(Reply::Json(json!({
+                "qt": "6.4.2",
+                "libtorrent": "2.0.9.0",
+                "boost": "1.83.0",
+                "openssl": "3.0.2",
+                "bitness": 64,
+            }))),
+            "app/preferences" => Ok(Reply::Json(self.preferences())),
+            "torrents/info" => self.info(&fields).map(Reply::Json),
+            "torrents/properties" => self.properties(&fields).map(Reply::Json),
+            "torrents/files" => self.files(&fields).map(Reply::Json),
+            "torrents/add" => self.add(&fields).map(|()| Reply::Text("Ok.")),
+            "torrents/delete" => self.delete(&fields).map(|()| Reply::Text("")),
+            "torrents/categories" => Ok(Reply::Json(self.categories())),
+            "torrents/createCategory" => self.create_category(&fields).map(|()| Reply::Text("")),
+            "torrents/setCategory" => self.set_category(&fields).map(|()| Reply::Text("")),
+            endpoint if IGNORED_ENDPOINTS.contains(&endpoint) => Ok(Reply::Text("")),
+            _ => return reply(request, 404, TEXT, "Not Found", &[]),
+        };
+
+        match outcome {
+            Ok(Reply::Text(text)) => reply(request, 200, TEXT, text, &[]),
+            Ok(Reply::Json(value)) => reply(request, 200, JSON, value.to_string(), &[]),
+            Err(e) => {
+                warn!("qbittorrent : {endpoint} : {e}");
+                let status = match e.failure() {
+                    Failure::InvalidInput => 400,
+                    Failure::NotFound => 404,
+                    _ => 500,
+                };
+                reply(request, status, TEXT, e.message().as_str(), &[]);
+            }
+        }
+    }
+
+    fn login(&self, request: Request, fields: &[Field]) {
+        let accepted = self.password.as_ref().is_none_or(|password| {
+            field(fields, "username").as_ref() == Some(&self.username)
+                && field(fields, "password").as_ref() == Some(password)
+        });
+
+        if !accepted {
+            warn!("qbittorrent : rejected login");
+            return reply(request, 200, TEXT, "Fails.", &[]);
+        }
+
+        let session = new_session();
+        let cookie = format!("SID={session}; HttpOnly; path=/");
+        self.sessions().insert(session);
+
+        reply(request, 200, TEXT, "Ok.", &[("Set-Cookie", cookie)]);
+    }
+
+    fn authorized(&self, request: &Request) -> bool {
+        self.password.is_none()
+            || session(request).is_some_and(|session| self.sessions().contains(&session))
+    }
+
+    fn sessions(&self) -> MutexGuard<'_, HashSet<String>> {
+        self.sessions.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn store(&self) -> MutexGuard<'_, Store> {
+        self.store.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn preferences(&self) -> Value {
+        json!({
+            "save_path": self.download_dir.to_string_lossy(),
+            "temp_path_enabled": false,
+            "max_ratio_enabled": false,
+            "max_ratio": -1,
+            "max_ratio_act": 0,
+            "max_seeding_time_enabled": false,
+            "max_seeding_time": -1,
+            "queueing_enabled": false,
+            "dht": false,
+        })
+    }
+
+    /// The Real-Debrid torrents, reused for `interval`.
+    fn listing(&self) -> Result<Value, ApiError> {
+        let mut cached = self.listing.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((at, listing)) = cached.as_ref()
+            && at.elapsed() < self.interval
+        {
+            return Ok(listing.clone());
+        }
+
+        let listing =
+            serde_json::from_str::<Value>(&torrents::get_all_torrents()?).unwrap_or_default();
+        *cached = Some((Instant::now(), listing.clone()));
+
+        Ok(listing)
+    }
+
+    /// Fetches the torrents listing again on the next request.
+    fn forget_listing(&self) {
+        *self.listing.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+
+    /// Every job with its qBittorrent torrent object.
+    fn torrents(&self) -> Result<Vec<(Job, Value)>, ApiError> {
+        let listing = self.listing()?;
+        let remote = listing
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|torrent| Some((torrent["id"].as_str()?, torrent)))
+            .collect::<HashMap<_, _>>();
+
+        let jobs = self.store().list(None)?;
+        let entries = jobs
+            .into_iter()
+            .map(|job| {
+                let remote = job
+                    .torrent()
+                    .as_deref()
+                    .and_then(|torrent| remote.get(torrent).copied());
+                let entry = self.entry(&job, remote);
+                (job, entry)
+            })
+            .collect();
+
+        Ok(entries)
+    }
+
+    /// The qBittorrent torrent object of a job.
+    fn entry(&self, job: &Job, remote: Option<&Value>) -> Value {
+        let remote = remote.cloned().unwrap_or_default();
+        let hash = remote["hash"]
+            .as_str()
+            .map(str::to_lowercase)
+            .or_else(|| magnet_hash(job.source()))
+            .unwrap_or_else(|| job_hash(*job.id()));
+        let size = remote["bytes"].as_u64().unwrap_or(0);
+
+        let (state, progress) = match job.state() {
+            JobState::Submitted => {
+                let state = match remote["status"].as_str().unwrap_or_default() {
+                    "magnet_conversion" | "waiting_files_selection" => "metaDL",
+                    "queued" => "queuedDL",
+                    "downloading" | "compressing" | "uploading" | "downloaded" => "downloading",
+                    _ => "stalledDL",
+                };
+                let remote_progress = remote["progress"].as_f64().unwrap_or(0.0) / 100.0;
+                (state, remote_progress / 2.0)
+            }
+            JobState::Downloading => {
+                let local_bytes = jobs::local_progress(&self.store(), job)
+                    .map(|(_, _, bytes)| bytes)
+                    .unwrap_or(0);
+                let local_progress = if size == 0 {
+                    0.0
+                } else {
+                    (local_bytes as f64 / size as f64).min(1.0)
+                };
+                ("downloading", 0.5 + local_progress / 2.0)
+            }
+            JobState::Done => ("pausedUP", 1.0),
+            JobState::Failed => ("error", 0.0),
+        };
+
+        let added_on = remote["added"]
+            .as_str()
+            .and_then(date::parse_rfc3339)
+            .unwrap_or(*job.updated());
+        let completion_on = match job.state() {
+            JobState::Done => *job.updated(),
+            _ => 0,
+        };
+
+        json!({
+            "hash": hash,
+            "name": job.name(),
+            "size": size,
+            "total_size": size,
+            "progress": progress,
+            "amount_left": (size as f64 * (1.0 - progress)) as u64,
+            "dlspeed": remote["speed"].as_u64().unwrap_or(0),
+            "upspeed": 0,
+            "eta": if *job.state() == JobState::Done { 0 } else { ETA_UNKNOWN },
+            "state": state,
+            "category": self.category_of(job.out_dir()),
+            "tags": "",
+            "save_path": job.out_dir().to_string_lossy(),
+            "content_path": self.content_path(job).to_string_lossy(),
+            "added_on": added_on,
+            "completion_on": completion_on,
+            "last_activity": *job.updated(),
+            "num_seeds": remote["seeders"].as_u64().unwrap_or(0),
+            "ratio": 0,
+            "ratio_limit": -2,
+            "seeding_time": 0,
+            "seeding_time_limit": -2,
+            "inactive_seeding_time_limit": -2,
+        })
+    }
+
+    /// Where the files of a job are downloaded, see the `jobs` module.
+    fn content_path(&self, job: &Job) -> PathBuf {
+        job.out_dir().join(sanitize_filename(job.name()))
+    }
+
+    /// The category of a job downloading into `<download-dir>/<category>`.
+    fn category_of(&self, out_dir: &Path) -> String {
+        match out_dir.parent() {
+            Some(parent) if parent == self.download_dir => out_dir
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            _ => String::new(),
+        }
+    }
+
+    /// The jobs matching the `hashes` field, `|` separated or `all`.
+    fn matching(&self, fields: &[Field], name: &str) -> Result<Vec<(Job, Value)>, ApiError> {
+        let hashes = field(fields, name).unwrap_or_default().to_lowercase();
+        let hashes = hashes.split('|').collect::<HashSet<_>>();
+
+        let mut torrents = self.torrents()?;
+        if !hashes.contains("all") {
+            torrents
+                .retain(|(_, entry)| hashes.contains(entry["hash"].as_str().unwrap_or_default()));
+        }
+
+        Ok(torrents)
+    }
+
+    /// The one job matching the `hash` field.
+    fn find(&self, fields: &[Field]) -> Result<(Job, Value), ApiError> {
+        self.matching(fields, "hash")?
+            .into_iter()
+            .next()
+            .ok_or_else(|| ApiError::new(Failure::NotFound, "Torrent hash was not found"))
+    }
+
+    fn info(&self, fields: &[Field]) -> Result<Value, ApiError> {
+        let category = field(fields, "category");
+        let torrents = match field(fields, "hashes") {
+            Some(_) => self.matching(fields, "hashes")?,
+            None => self.torrents()?,
+        };
+
+        Ok(torrents
+            .into_iter()
+            .map(|(_, entry)| entry)
+            .filter(|entry| {
+                category
+                    .as_ref()
+                    .is_none_or(|category| entry["category"] == category.as_str())
+            })
+            .collect())
+    }
+
+    fn properties(&self, fields: &[Field]) -> Result<Value, ApiError> {
+        let (_, entry) = self.find(fields)?;
+
+        Ok(json!({
+            "save_path": entry["save_path"],
+            "total_size": entry["size"],
+            "addition_date": entry["added_on"],
+            "completion_date": entry["completion_on"],
+            "dl_speed": entry["dlspeed"],
+            "eta": entry["eta"],
+            "share_ratio": 0,
+            "seeding_time": 0,
+            "seeds": entry["num_seeds"],
+        }))
+    }
+
+    fn files(&self, fields: &[Field]) -> Result<Value, ApiError> {
+        let (job, entry) = self.find(fields)?;
+        let Some(torrent) = job.torrent().clone() else {
+            return Ok(json!([]));
+        };
+
+        let info = serde_json::from_str::<Value>(&torrents::get_torrent_info(torrent)?)
+            .unwrap_or_default();
+        let root = sanitize_filename(job.name());
+
+        Ok(info["files"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|file| file["selected"] == 1)
+            .enumerate()
+            .map(|(index, file)| {
+                let path = file["path"].as_str().unwrap_or_default();
+                json!({
+                    "index": index,
+                    "name": format!("{root}/{}", path.trim_start_matches('/')),
+                    "size": file["bytes"],
+                    "progress": entry["progress"],
+                    "priority": 1,
+                    "is_seed": false,
+                })
+            })
+            .collect())
+    }
+
+    fn add(&self, fields: &[Field]) -> Result<(), ApiError> {
+        let category = field(fields, "category").filter(|category| !category.is_empty());
+        let out_dir = match (field(fields, "savepath"), category) {
+            (Some(save_path), _) if !save_path.is_empty() => PathBuf::from(save_path),
+            (_, Some(category)) => self.download_dir.join(sanitize_filename(&category)),
+            _ => self.download_dir.clone(),
+        };
+
+        let mut sources = Vec::new();
+        for url in field(fields, "urls").unwrap_or_default().lines() {
+            let url = url.trim();
+            if !url.is_empty() {
+                sources.push(fetch_source(url)?);
+            }
+        }
+        sources.extend(
+            fields
+                .iter()
+                .filter(|field| field.name == "torrents" && field.filename.is_some())
+                .map(|field| Source::TorrentFile(field.value.clone())),
+        );
+
+        if sources.is_empty() {
+            return Err(ApiError::new(
+                Failure::InvalidInput,
+                "Fails. no `urls` or `torrents` given",
+            ));
+        }
+
+        for source in sources {
+            let job = jobs::submit(&self.store(), source, None, &out_dir, self.select)?;
+            info!("qbittorrent : added `{}`", job.name());
+        }
+        self.forget_listing();
+
+        Ok(())
+    }
+
+    fn delete(&self, fields: &[Field]) -> Result<(), ApiError> {
+        let delete_files = field(fields, "deleteFiles").is_some_and(|value| value == "true");
+
+        for (job, _) in self.matching(fields, "hashes")? {
+            if let Some(torrent) = job.torrent().clone() {
+                match torrents::delete(torrent) {
+                    Err(e) if *e.failure() != Failure::NotFound => return Err(e),
+                    _ => (),
+                }
+            }
+
+            let content_path = self.content_path(&job);
+            if delete_files
+                && let Err(e) = fs::remove_dir_all(&content_path)
+                && e.kind() != io::ErrorKind::NotFound
+            {
+                warn!("qbittorrent : `{}` : {e}", content_path.display());
+            }
+
+            self.store().delete(*job.id())?;
+            info!("qbittorrent : deleted `{}`", job.name());
+        }
+
+        Ok(())
+    }
+
+    /// Every folder of the download directory is a category.
+    fn categories(&self) -> Value {
+        let categories = fs::read_dir(&self.download_dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter(|entry| entry.path().is_dir())
+            .map(|entry| {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                let category = json!({
+                    "name": name,
+                    "savePath": entry.path().to_string_lossy(),
+                });
+                (name, category)
+            })
+            .collect::<serde_json::Map<_, _>>();
+
+        Value::Object(categories)
+    }
+
+    fn create_category(&self, fields: &[Field]) -> Result<(), ApiError> {
+        let category = field(fields, "category")
+            .filter(|category| !category.is_empty())
+            .ok_or_else(|| ApiError::new(Failure::InvalidInput, "Missing category"))?;
+
+        let dir = self.download_dir.join(sanitize_filename(&category));
+        fs::create_dir_all(&dir)
+            .map_err(|e| ApiError::new(Failure::Api, format!("`{}` : {e}", dir.display())))
+    }
+
+    fn set_category(&self, fields: &[Field]) -> Result<(), ApiError> {
+        let category = field(fields, "category").unwrap_or_default();
+        let out_dir = match category.as_str() {
+            "" => self.download_dir.clone(),
+            category => self.download_dir.join(sanitize_filename(category)),
+        };
+
+        for (job, _) in self.matching(fields, "hashes")? {
+            self.store().set_out_dir(*job.id(), &out_dir)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The session id in the `SID` cookie of a request.
+fn session(request: &Request) -> Option<String> {
+    header(request, "Cookie")?
+        .split(';')
+        .find_map(|cookie| cookie.trim().strip_prefix("SID="))
+        .map(String::from)
+}
+
+/// A new unguessable session id.
+fn new_session() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+
+    // Every `RandomState` is seeded from the system's randomness.
+    (0..2)
+        .map(|_| {
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_u128(nanos);
+            format!("{:016x}", hasher.finish())
+        })
+        .collect()
+}
+
+/// The info hash of a magnet link, from its `xt=urn:btih:` parameter.
+fn magnet_hash(source: &Source) -> Option<String> {
+    let Source::Magnet(magnet) = source else {
+        return None;
+    };
+
+    magnet
+        .split(['?', '&'])
+        .find_map(|parameter| parameter.strip_prefix("xt=urn:btih:"))
+        .map(str::to_lowercase)
+}
+
+/// A stable info hash for a job whose hash is not known, made from its id.
+fn job_hash(id: i64) -> String {
+    format!("{id:040x}")
+}
+
+/// Reads a magnet link, or fetches a `.torrent` url.
+///
+/// Indexers often redirect `.torrent` urls to magnet links.
+fn fetch_source(url: &str) -> Result<Source, ApiError> {
+    let mut url = url.to_string();
+
+    for _ in 0..=MAX_REDIRECTS {
+        if url.starts_with("magnet:") {
+            return Ok(Source::Magnet(url));
+        }
+
+        let response = TORRENT_CLIENT.get(&url).send()?;
+        let status = response.status();
+
+        if status.is_redirection() {
+            url = response
+                .headers()
+                .get(LOCATION)
+                .and_then(|location| location.to_str().ok())
+                .and_then(|location| response.url().join(location).ok())
+                .map(String::from)
+                .ok_or_else(|| {
+                    ApiError::new(Failure::InvalidInput, format!("`{url}` : bad redirect"))
+                })?;
+            continue;
+        }
+
+        if !status.is_success() {
+            return Err(ApiError::new(
+                Failure::InvalidInput,
+                format!("`{url}` : {status}"),
+            ));
+        }
+
+        let torrent = response.bytes()?;
+        return Ok(Source::TorrentFile(torrent.to_vec()));
+    }
+
+    Err(ApiError::new(
+        Failure::InvalidInput,
+        format!("`{url}` : too many redirects"),
+    ))
+}
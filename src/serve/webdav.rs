@@ -0,0 +1,372 @@
+//! # WebDAV Module
+//!
+//! This module serves the downloaded torrents as a read-only WebDAV file tree,
+//! for file managers and media players.
+//!
+//! Every downloaded torrent is a folder of the root, see the `library` module.
+//!
+//! The link of a file is unrestricted on its first access, and the unrestricted
+//! link is reused for `--link-ttl` seconds, or until Real-Debrid stops serving it.
+//! `GET` requests, including their `Range`, are proxied to the unrestricted link,
+//! so players can seek.
+
+use std::io;
+use std::time::Duration;
+
+use reqwest::header::{CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, RANGE};
+use tiny_http::{Header, Request, Response};
+
+use super::{header, percent_encode, reply, split_url};
+use crate::app::WebdavArgs;
+use crate::date;
+use crate::error::ApiError;
+use crate::fetch::DOWNLOAD_CLIENT;
+use crate::library::{Cache, File, Folder, Links};
+
+const XML: &str = "application/xml; charset=utf-8";
+const HTML: &str = "text/html; charset=utf-8";
+
+const ALLOWED_METHODS: &str = "OPTIONS, PROPFIND, GET, HEAD";
+
+/// A node of the file tree.
+enum Node {
+    Root(Vec<Folder>),
+    Folder(Folder, Vec<File>),
+    File(Folder, File),
+}
+
+struct Dav {
+    library: Cache,
+    links: Links,
+}
+
+/// Serves the WebDAV file tree on `listen` until the server stops.
+pub fn run(listen: &str, workers: usize, args: &WebdavArgs) -> Result<(), ApiError> {
+    let dav = Dav {
+        library: Cache::default(),
+        links: Links::new(Duration::from_secs(*args.link_ttl())),
+    };
+
+    let http = super::listen(listen)?;
+    super::serve(&http, workers, |request| dav.handle(request));
+
+    Ok(())
+}
+
+impl Dav {
+    fn handle(&self, request: Request) {
+        let (path, _) = split_url(request.url());
+        let method = request.method().to_string().to_uppercase();
+
+        if method == "OPTIONS" {
+            return reply(
+                request,
+                200,
+                "text/plain",
+                "",
+                &[
+                    ("DAV", String::from("1")),
+                    ("Allow", String::from(ALLOWED_METHODS)),
+                    ("MS-Author-Via", String::from("DAV")),
+                ],
+            );
+        }
+        if !matches!(method.as_str(), "PROPFIND" | "GET" | "HEAD") {
+            return reply(
+                request,
+                405,
+                "text/plain",
+                "read-only",
+                &[("Allow", String::from(ALLOWED_METHODS))],
+            );
+        }
+
+        let node = match self.resolve(&path) {
+            Ok(Some(node)) => node,
+            Ok(None) => return reply(request, 404, "text/plain", "Not Found", &[]),
+            Err(e) => {
+                warn!("webdav : {method} {path} : {e}");
+                return reply(request, 502, "text/plain", e.message().as_str(), &[]);
+            }
+        };
+
+        match (method.as_str(), node) {
+            ("PROPFIND", node) => {
+                let depth_zero = header(&request, "Depth").as_deref() == Some("0");
+                reply(request, 207, XML, multistatus(&node, depth_zero), &[]);
+            }
+            (_, Node::File(_, file)) => self.proxy(request, &file),
+            (_, node) => reply(request, 200, HTML, index(&node), &[]),
+        }
+    }
+
+    /// Finds the node at a decoded request path.
+    fn resolve(&self, path: &str) -> Result<Option<Node>, ApiError> {
+        let segments = path
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .collect::<Vec<_>>();
+
+        let folders = self.library.folders()?;
+        let Some(folder_name) = segments.first() else {
+            return Ok(Some(Node::Root(folders)));
+        };
+
+        let Some(folder) = folders
+            .into_iter()
+            .find(|folder| folder.name == *folder_name)
+        else {
+            return Ok(None);
+        };
+        let files = self.library.files(&folder.id)?;
+
+        Ok(match segments.get(1..) {
+            Some([]) => Some(Node::Folder(folder, files)),
+            Some([file_name]) => files
+                .into_iter()
+                .find(|file| file.name == *file_name)
+                .map(|file| Node::File(folder, file)),
+            _ => None,
+        })
+    }
+
+    /// Proxies a `GET` or `HEAD` request for a file to its unrestricted link.
+    fn proxy(&self, request: Request, file: &File) {
+        let head = request.method().to_string().eq_ignore_ascii_case("HEAD");
+        if head {
+            let headers = [
+                plain_header("Accept-Ranges", "bytes"),
+                plain_header("Content-Type", content_type(&file.name)),
+            ]
+            .into_iter()
+            .flatten()
+            .collect();
+            let response = Response::new(
+                200.into(),
+                headers,
+                io::empty(),
+                Some(file.bytes as usize),
+                None,
+            );
+            if let Err(e) = request.respond(response) {
+                warn!("webdav : HEAD `{}` : {e}", file.name);
+            }
+            return;
+        }
+
+        let range = header(&request, "Range");
+
+        // An expired link is unrestricted again, once.
+        let mut upstream = None;
+        for _ in 0..2 {
+            let fetched = self.links.download_url(file).and_then(|url| {
+                let mut get = DOWNLOAD_CLIENT.get(url);
+                if let Some(range) = &range {
+                    get = get.header(RANGE, range);
+                }
+                Ok(get.send()?)
+            });
+
+            match fetched {
+                Ok(response)
+                    if response.status().is_client_error() && response.status().as_u16() != 416 =>
+                {
+                    debug!("webdav : `{}` : link expired", file.name);
+                    self.links.expire(file);
+                }
+                fetched => {
+                    upstream = Some(fetched);
+                    break;
+                }
+            }
+        }
+
+        let response = match upstream {
+            Some(Ok(response)) => response,
+            Some(Err(e)) => {
+                warn!("webdav : GET `{}` : {e}", file.name);
+                return reply(request, 502, "text/plain", e.message().as_str(), &[]);
+            }
+            None => return reply(request, 502, "text/plain", "link expired", &[]),
+        };
+
+        let status = response.status().as_u16();
+        let length = response
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|length| length.to_str().ok()?.parse::<usize>().ok());
+
+        let mut headers = plain_header("Accept-Ranges", "bytes")
+            .into_iter()
+            .collect::<Vec<_>>();
+        for (name, fallback) in [
+            (CONTENT_TYPE, Some(content_type(&file.name))),
+            (CONTENT_RANGE, None),
+        ] {
+            let value = response
+                .headers()
+                .get(&name)
+                .and_then(|value| value.to_str().ok())
+                .or(fallback);
+            headers.extend(value.and_then(|value| plain_header(name.as_str(), value)));
+        }
+
+        let response = Response::new(status.into(), headers, response, length, None);
+        if let Err(e) = request.respond(response) {
+            // Players routinely drop connections when seeking.
+            debug!("webdav : GET `{}` : {e}", file.name);
+        }
+    }
+}
+
+fn plain_header(name: &str, value: &str) -> Option<Header> {
+    Header::from_bytes(name.as_bytes(), value.as_bytes()).ok()
+}
+
+/// A content type guessed from a file name.
+fn content_type(name: &str) -> &'static str {
+    let extension = name.rsplit('.').next().unwrap_or_default().to_lowercase();
+
+    match extension.as_str() {
+        "mkv" => "video/x-matroska",
+        "mp4" | "m4v" => "video/mp4",
+        "avi" => "video/x-msvideo",
+        "webm" => "video/webm",
+        "mov" => "video/quicktime",
+        "ts" => "video/mp2t",
+        "mp3" => "audio/mpeg",
+        "flac" => "audio/flac",
+        "srt" => "application/x-subrip",
+        "nfo" | "txt" => "text/plain",
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "zip" => "application/zip",
+        "rar" => "application/vnd.rar",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Escapes text for XML and HTML.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// A PROPFIND `response` element.
+fn response_xml(href: &str, name: &str, bytes: Option<u64>, modified: i64) -> String {
+    let (resource_type, length) = match bytes {
+        Some(bytes) => (
+            String::new(),
+            format!("<D:getcontentlength>{bytes}</D:getcontentlength>"),
+        ),
+        None => (String::from("<D:collection/>"), String::new()),
+    };
+
+    format!(
+        "<D:response><D:href>{}</D:href><D:propstat><D:prop>\
+         <D:displayname>{}</D:displayname>\
+         <D:resourcetype>{resource_type}</D:resourcetype>{length}\
+         <D:getlastmodified>{}</D:getlastmodified>\
+         </D:prop><D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>",
+        escape(href),
+        escape(name),
+        date::http_date(modified),
+    )
+}
+
+/// The PROPFIND reply for a node, and its children unless `depth_zero`.
+fn multistatus(node: &Node, depth_zero: bool) -> String {
+    let mut responses = Vec::new();
+
+    match node {
+        Node::Root(folders) => {
+            responses.push(response_xml("/", "", None, 0));
+            if !depth_zero {
+                for folder in folders {
+                    let href = format!("/{}/", percent_encode(&folder.name));
+                    responses.push(response_xml(&href, &folder.name, None, folder.added));
+                }
+            }
+        }
+        Node::Folder(folder, files) => {
+            let folder_href = format!("/{}/", percent_encode(&folder.name));
+            responses.push(response_xml(&folder_href, &folder.name, None, folder.added));
+            if !depth_zero {
+                for file in files {
+                    let href = format!("{folder_href}{}", percent_encode(&file.name));
+                    responses.push(response_xml(
+                        &href,
+                        &file.name,
+                        Some(file.bytes),
+                        folder.added,
+                    ));
+                }
+            }
+        }
+        Node::File(folder, file) => {
+            let href = format!(
+                "/{}/{}",
+                percent_encode(&folder.name),
+                percent_encode(&file.name)
+            );
+            responses.push(response_xml(
+                &href,
+                &file.name,
+                Some(file.bytes),
+                folder.added,
+            ));
+        }
+    }
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:multistatus xmlns:D=\"DAV:\">{}</D:multistatus>",
+        responses.concat()
+    )
+}
+
+/// A plain HTML listing of a folder, for browsers.
+fn index(node: &Node) -> String {
+    let (title, entries) = match node {
+        Node::Root(folders) => (
+            String::from("/"),
+            folders
+                .iter()
+                .map(|folder| {
+                    (
+                        format!("{}/", percent_encode(&folder.name)),
+                        format!("{}/", folder.name),
+                        folder.bytes,
+                    )
+                })
+                .collect::<Vec<_>>(),
+        ),
+        Node::Folder(folder, files) => (
+            format!("/{}/", folder.name),
+            files
+                .iter()
+                .map(|file| (percent_encode(&file.name), file.name.clone(), file.bytes))
+                .collect(),
+        ),
+        Node::File(..) => (String::new(), Vec::new()),
+    };
+
+    let items = entries
+        .iter()
+        .map(|(href, name, bytes)| {
+            format!(
+                "<li><a href=\"{}\">{}</a> {}</li>",
+                escape(href),
+                escape(name),
+                crate::size::humanize(*bytes)
+            )
+        })
+        .collect::<String>();
+
+    format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>{0}</title></head>\
+         <body><h1>{0}</h1><ul>{items}</ul></body></html>",
+        escape(&title)
+    )
+}
@@ -7,52 +7,53 @@ const CHANGE_PASSWORD_URL: &str = "https://api.real-debrid.com/rest/1.0/settings
 const AVATAR_FILE_URL: &str = "https://api.real-debrid.com/rest/1.0/settings/avatarFile";
 const AVATAR_DELETE_URL: &str = "https://api.real-debrid.com/rest/1.0/settings/avatarDelete";
 
-pub fn get_settings() -> Json {
+pub fn get_settings() -> ApiResult {
     send(Get(""), SETTINGS_URL)
 }
 
-pub fn update(setting_name: String, setting_value: String) -> Json {
-    let body = format!(
-        "{} 'setting_name': '{}', 'setting_value' {} {}",
-        '{', setting_name, setting_value, '}'
-    );
+pub fn update(setting_name: String, setting_value: String) -> ApiResult {
+    let body = serde_json::json!({
+        "setting_name": setting_name,
+        "setting_value": setting_value,
+    })
+    .to_string();
 
     send(Post(body), UPDATE_URL)
 }
 
-pub fn convert_points() -> Json {
+pub fn convert_points() -> ApiResult {
     send(Post(""), CONVERT_POINTS_URL)
 }
 
-pub fn change_password() -> Json {
+pub fn change_password() -> ApiResult {
     send(Post(""), CHANGE_PASSWORD_URL)
 }
 
-pub fn avatar_file() -> Json {
+pub fn avatar_file() -> ApiResult {
     send(Put(""), AVATAR_FILE_URL)
 }
 
-pub fn avatar_delete() -> Json {
+pub fn avatar_delete() -> ApiResult {
     send(Delete(""), AVATAR_DELETE_URL)
 }
 
-/* pub fn link(link: String) -> Json {
+/* pub fn link(link: String) -> ApiResult {
     let body = format!("{} 'link': '{}' {}", '{', link, '}');
 
     send(Post(body), LINK_URL)
 }
 
-pub fn folder(link: String) -> Json {
+pub fn folder(link: String) -> ApiResult {
     let body = format!("{} 'link': '{}' {}", '{', link, '}');
 
     send(Post(body), FOLDER_URL)
 }
 
-pub fn container_file() -> Json {
+pub fn container_file() -> ApiResult {
     send(Put(""), CONTAINER_FILE_URL)
 }
 
-pub fn container_link(link: String) -> Json {
+pub fn container_link(link: String) -> ApiResult {
     let body = format!("{} 'link': '{}' {}", '{', link, '}');
 
     send(Post(body), CONTAINER_LINK_URL)
@@ -0,0 +1,88 @@
+//! # Size Module
+//!
+//! This module parses and humanizes byte sizes.
+//!
+//! Decimal units (`KB`, `MB`, `GB`, `TB`) are powers of 1000,
+//! binary units (`KiB`, `MiB`, `GiB`, `TiB`) are powers of 1024.
+//! Units are case-insensitive and a bare number is in bytes.
+
+const UNITS: [(&str, f64); 9] = [
+    ("b", 1.0),
+    ("kb", 1e3),
+    ("mb", 1e6),
+    ("gb", 1e9),
+    ("tb", 1e12),
+    ("kib", 1024.0),
+    ("mib", 1_048_576.0),
+    ("gib", 1_073_741_824.0),
+    ("tib", 1_099_511_627_776.0),
+];
+
+const BINARY_UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+/// Parses a size like `700MB` or `1.5 GiB` into bytes.
+pub fn parse(size: &str) -> Result<u64, String> {
+    let size = size.trim();
+    let unit_at = size
+        .find(|c: char| c.is_ascii_alphabetic())
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(unit_at);
+
+    let number = number
+        .trim()
+        .parse::<f64>()
+        .map_err(|e| format!("invalid size `{size}` : {e}"))?;
+    if number < 0.0 {
+        return Err(format!("invalid size `{size}` : sizes cannot be negative"));
+    }
+
+    let unit = unit.to_lowercase();
+    let (_, multiplier) = UNITS
+        .iter()
+        .find(|(name, _)| unit.is_empty() || *name == unit)
+        .ok_or_else(|| format!("invalid size unit `{unit}`"))?;
+
+    Ok((number * multiplier).round() as u64)
+}
+
+/// Formats bytes with the largest fitting binary unit, like `1.5 GiB`.
+pub fn humanize(bytes: u64) -> String {
+    let mut size = bytes as f64;
+    let mut unit = 0;
+
+    while size >= 1024.0 && unit < BINARY_UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    match unit {
+        0 => format!("{bytes} B"),
+        _ => format!("{size:.1} {}", BINARY_UNITS[unit]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_are_parsed() {
+        assert_eq!(parse("700MB"), Ok(700_000_000));
+        assert_eq!(parse("1.5 GiB"), Ok(1_610_612_736));
+        assert_eq!(parse("4kib"), Ok(4096));
+        assert_eq!(parse("42"), Ok(42));
+    }
+
+    #[test]
+    fn bad_sizes_are_rejected() {
+        assert!(parse("-1GB").is_err());
+        assert!(parse("3 parsecs").is_err());
+        assert!(parse("GB").is_err());
+    }
+
+    #[test]
+    fn sizes_are_humanized() {
+        assert_eq!(humanize(512), "512 B");
+        assert_eq!(humanize(1_610_612_736), "1.5 GiB");
+    }
+}
@@ -1,13 +1,13 @@
 use crate::prelude::*;
 
 const TRANSCODE_URL: &str = "https://api.real-debrid.com/rest/1.0/streaming/transcode/";
-const MEDIA_INFOS_URL: &str = "https://api.real-debrid.com/rest/1.0/streaming/mediInfos/";
+const MEDIA_INFOS_URL: &str = "https://api.real-debrid.com/rest/1.0/streaming/mediaInfos/";
 
 type Id = String;
-pub fn transcode(id: Id) -> Json {
+pub fn transcode(id: Id) -> ApiResult {
     send(Get(""), format!("{TRANSCODE_URL}{id}"))
 }
 
-pub fn media_infos(id: Id) -> Json {
+pub fn media_infos(id: Id) -> ApiResult {
     send(Get(""), format!("{MEDIA_INFOS_URL}{id}"))
 }
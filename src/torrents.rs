@@ -9,44 +9,52 @@ const ADD_MAGNET_URL: &str = "https://api.real-debrid.com/rest/1.0/torrents/addM
 const SELECT_FILES_URL: &str = "https://api.real-debrid.com/rest/1.0/torrents/selectFiles/";
 const DELETE_URL: &str = "https://api.real-debrid.com/rest/1.0/torrents/delete/";
 
-pub fn get_torrents() -> Json {
+pub fn get_torrents() -> ApiResult {
     send(Get("{ {offset} : {8} }"), TORRENTS_URL)
 }
 
+pub fn get_all_torrents() -> ApiResult {
+    get_all_pages(TORRENTS_URL)
+}
+
 type Id = String;
-pub fn get_torrent_info(id: Id) -> Json {
+pub fn get_torrent_info(id: Id) -> ApiResult {
     send(Get(""), format!("{TORRENT_INFO_URL}{id}"))
 }
 
-pub fn get_active_count() -> Json {
+pub fn get_active_count() -> ApiResult {
     send(Get(""), ACTIVE_COUNT_URL)
 }
 
-pub fn get_available_hosts() -> Json {
+pub fn get_available_hosts() -> ApiResult {
     send(Get(""), AVAILABLE_HOSTS_URL)
 }
 
 type Host = String;
-pub fn add_torrent(host: Host) -> Json {
-    let body = format!("{} \"host\": \"{}\" {}", '{', host, '}');
+/// Uploads the contents of a `.torrent` file, optionally to a specific host.
+pub fn add_torrent(torrent: Vec<u8>, host: Option<Host>) -> ApiResult {
+    let url = match host {
+        Some(host) => format!("{ADD_TORRENT_URL}?host={host}"),
+        None => ADD_TORRENT_URL.to_string(),
+    };
 
-    send(Put(body), ADD_TORRENT_URL)
+    send_file(torrent, "application/x-bittorrent", url)
 }
 
 type Link = String;
-pub fn add_magnet(link: Link) -> Json {
+pub fn add_magnet(link: Link) -> ApiResult {
     let body = format!("{} \"magnet\": \"{}\" {}", '{', link, '}');
 
     send(Post(body), ADD_MAGNET_URL)
 }
 
 type Files = String;
-pub fn select_files(id: Id, files: Files) -> Json {
+pub fn select_files(id: Id, files: Files) -> ApiResult {
     let body = format!("{} \"files\": \"{}\" {}", '{', files, '}');
 
     send(Post(body), format!("{SELECT_FILES_URL}{id}"))
 }
 
-pub fn delete(id: Id) -> Json {
+pub fn delete(id: Id) -> ApiResult {
     send(Delete(""), format!("{DELETE_URL}{id}"))
 }
@@ -0,0 +1,398 @@
+//! # Trace Module
+//!
+//! This module records every HTTP exchange with the API.
+//!
+//! ## `--trace-http`
+//!
+//! Logs the method, URL, status, timing, headers and bodies
+//! of every exchange as `trace` records.
+//!
+//! ## `--har <PATH>`
+//!
+//! Writes every exchange into a HAR 1.2 file, which can be
+//! shared with support or opened in browser developer tools.
+//! Each exchange is appended as it completes, rewriting only the closing
+//! brackets, so the file stays valid even when the process exits early.
+//!
+//! ## Redaction
+//!
+//! The API key, the `Authorization` header, and any field named
+//! like a token or password are replaced by `<redacted>` before
+//! anything is logged or written.
+
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{self, Seek, SeekFrom, Write};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use reqwest::blocking::Request as ReqwestRequest;
+use reqwest::header::{AUTHORIZATION, CONTENT_TYPE, HeaderMap};
+use reqwest::{StatusCode, Url as ReqwestUrl};
+use serde_json::{Value, json};
+
+use crate::date::rfc3339;
+use crate::{API_KEY, ARGS};
+
+const REDACTED: &str = "<redacted>";
+
+/// Field names whose values are never recorded.
+const SECRET_FIELDS: [&str; 6] = [
+    "password",
+    "token",
+    "access_token",
+    "refresh_token",
+    "client_secret",
+    "code",
+];
+
+/// What follows the last entry of a HAR file.
+const HAR_TAIL: &str = "]}}";
+
+/// The HAR file being written, once the first exchange is recorded.
+static HAR: Mutex<Option<Har>> = Mutex::new(None);
+
+struct Har {
+    file: File,
+    entries: usize,
+    /// Where `HAR_TAIL` starts.
+    end: u64,
+}
+impl Har {
+    /// Creates the file at `path`, with no entries yet.
+    fn create(path: &str) -> io::Result<Self> {
+        let creator = json!({ "name": "traffic_cone", "version": env!("CARGO_PKG_VERSION") });
+        let head = format!(r#"{{"log":{{"version":"1.2","creator":{creator},"entries":["#);
+
+        let mut file = File::create(path)?;
+        file.write_all(format!("{head}{HAR_TAIL}").as_bytes())?;
+
+        Ok(Self {
+            file,
+            entries: 0,
+            end: head.len() as u64,
+        })
+    }
+
+    /// Writes `entry` over the tail, then the tail after it.
+    fn append(&mut self, entry: &Value) -> io::Result<()> {
+        let separator = if self.entries == 0 { "" } else { "," };
+        let entry = format!("{separator}{entry}");
+
+        self.file.seek(SeekFrom::Start(self.end))?;
+        self.file
+            .write_all(format!("{entry}{HAR_TAIL}").as_bytes())?;
+        self.entries += 1;
+        self.end += entry.len() as u64;
+
+        Ok(())
+    }
+}
+
+/// Whether exchanges are recorded at all.
+fn enabled() -> bool {
+    !crate::complete::is_completing() && (*ARGS.trace_http() || ARGS.har().is_some())
+}
+
+fn is_secret(name: &str) -> bool {
+    SECRET_FIELDS.contains(&name.to_lowercase().as_str())
+}
+
+/// Replaces every occurrence of the API key in `text`.
+fn redact_text(text: &str) -> String {
+    match API_KEY.as_str() {
+        "" => text.to_string(),
+        key => text.replace(key, REDACTED),
+    }
+}
+
+/// Redacts secret values in url-encoded `name=value` pairs.
+///
+/// The API key is left to `redact_text`, as it is not changed by url-encoding.
+fn redact_pairs(pairs: &str) -> String {
+    let mut url = ReqwestUrl::parse("data:,").expect("static url is valid");
+    url.set_query(Some(pairs));
+
+    let redacted = url
+        .query_pairs()
+        .map(|(name, value)| {
+            let value = if is_secret(&name) {
+                Cow::Borrowed(REDACTED)
+            } else {
+                value
+            };
+            (name.into_owned(), value.into_owned())
+        })
+        .collect::<Vec<_>>();
+
+    url.query_pairs_mut().clear().extend_pairs(redacted);
+    url.query().unwrap_or_default().to_string()
+}
+
+/// Redacts secret values in a JSON document, leaving other bodies as they are.
+///
+/// The API key is left to `redact_text`.
+fn redact_body(body: &str) -> String {
+    fn redact_value(value: &mut Value) {
+        match value {
+            Value::Object(map) => map.iter_mut().for_each(|(name, value)| {
+                if is_secret(name) {
+                    *value = Value::from(REDACTED);
+                } else {
+                    redact_value(value)
+                }
+            }),
+            Value::Array(values) => values.iter_mut().for_each(redact_value),
+            _ => (),
+        }
+    }
+
+    match serde_json::from_str::<Value>(body) {
+        Ok(mut value) => {
+            redact_value(&mut value);
+            value.to_string()
+        }
+        Err(_) => body.to_string(),
+    }
+}
+
+fn redact_url(url: &ReqwestUrl) -> String {
+    let mut url = url.clone();
+    if let Some(query) = url.query().map(redact_pairs) {
+        url.set_query(Some(&query));
+    }
+
+    redact_text(url.as_str())
+}
+
+fn redact_headers(headers: &HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = if name == AUTHORIZATION {
+                format!("Bearer {REDACTED}")
+            } else {
+                redact_text(value.to_str().unwrap_or("<binary>"))
+            };
+            (name.to_string(), value)
+        })
+        .collect()
+}
+
+fn har_pairs(pairs: &[(String, String)]) -> Value {
+    pairs
+        .iter()
+        .map(|(name, value)| json!({ "name": name, "value": value }))
+        .collect()
+}
+
+/// The redacted request half of an exchange.
+pub(crate) struct Exchange {
+    started: SystemTime,
+    method: String,
+    url: String,
+    query: Vec<(String, String)>,
+    headers: Vec<(String, String)>,
+    mime_type: String,
+    body: String,
+}
+impl Exchange {
+    /// Captures a request before it is sent, if exchanges are recorded.
+    pub(crate) fn start(request: &ReqwestRequest) -> Option<Self> {
+        if !enabled() {
+            return None;
+        }
+
+        let body = request
+            .body()
+            .and_then(|body| body.as_bytes())
+            .map(String::from_utf8_lossy)
+            .unwrap_or_default();
+
+        let mime_type = request
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+
+        let body = match mime_type.as_str() {
+            "application/x-www-form-urlencoded" => redact_text(&redact_pairs(&body)),
+            "application/x-bittorrent" => format!("<{} bytes>", body.len()),
+            _ => redact_text(&redact_body(&body)),
+        };
+
+        let url = redact_url(request.url());
+        let query = ReqwestUrl::parse(&url)
+            .map(|url| url.query_pairs().into_owned().collect())
+            .unwrap_or_default();
+
+        let exchange = Self {
+            started: SystemTime::now(),
+            method: request.method().to_string(),
+            url,
+            query,
+            headers: redact_headers(request.headers()),
+            mime_type,
+            body,
+        };
+
+        if *ARGS.trace_http() {
+            trace!(
+                "> {} {}\n> headers {:?}\n> body {}",
+                exchange.method, exchange.url, exchange.headers, exchange.body
+            );
+        }
+
+        Some(exchange)
+    }
+
+    /// Records the response to this exchange.
+    pub(crate) fn finish(
+        self,
+        status: StatusCode,
+        headers: &HeaderMap,
+        body: &str,
+        time: Duration,
+    ) {
+        let headers = redact_headers(headers);
+        let body = redact_text(&redact_body(body));
+
+        if *ARGS.trace_http() {
+            trace!(
+                "< {status} in {}ms\n< headers {headers:?}\n< body {body}",
+                time.as_millis()
+            );
+        }
+
+        let mime_type = headers
+            .iter()
+            .find(|(name, _)| name == CONTENT_TYPE.as_str())
+            .map(|(_, value)| value.clone())
+            .unwrap_or_default();
+
+        let response = json!({
+            "status": status.as_u16(),
+            "statusText": status.canonical_reason().unwrap_or_default(),
+            "httpVersion": "HTTP/1.1",
+            "cookies": [],
+            "headers": har_pairs(&headers),
+            "content": { "size": body.len(), "mimeType": mime_type, "text": body },
+            "redirectURL": "",
+            "headersSize": -1,
+            "bodySize": body.len(),
+        });
+
+        self.record(response, time);
+    }
+
+    /// Records a request that never received a response.
+    pub(crate) fn fail(self, error: &reqwest::Error, time: Duration) {
+        let error = redact_text(&error.to_string());
+
+        if *ARGS.trace_http() {
+            trace!("< failed in {}ms : {error}", time.as_millis());
+        }
+
+        let response = json!({
+            "status": 0,
+            "statusText": "",
+            "httpVersion": "",
+            "cookies": [],
+            "headers": [],
+            "content": { "size": 0, "mimeType": "" },
+            "redirectURL": "",
+            "headersSize": -1,
+            "bodySize": -1,
+            "_error": error,
+        });
+
+        self.record(response, time);
+    }
+
+    /// Appends the exchange to the HAR file, if one was requested.
+    fn record(self, response: Value, time: Duration) {
+        let Some(path) = ARGS.har() else {
+            return;
+        };
+
+        let mut request = json!({
+            "method": self.method,
+            "url": self.url,
+            "httpVersion": "HTTP/1.1",
+            "cookies": [],
+            "headers": har_pairs(&self.headers),
+            "queryString": har_pairs(&self.query),
+            "headersSize": -1,
+            "bodySize": self.body.len(),
+        });
+        if !self.body.is_empty() {
+            request["postData"] = json!({ "mimeType": self.mime_type, "text": self.body });
+        }
+
+        let time = time.as_secs_f64() * 1_000.0;
+        let entry = json!({
+            "startedDateTime": rfc3339(self.started),
+            "time": time,
+            "request": request,
+            "response": response,
+            "cache": {},
+            "timings": { "send": 0, "wait": time, "receive": 0 },
+        });
+
+        let mut har = HAR.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let written = match har.as_mut() {
+            Some(har) => har.append(&entry),
+            None => Har::create(path).and_then(|created| har.insert(created).append(&entry)),
+        };
+
+        let _ = written.inspect_err(|e| warn!("har : could not write `{path}` : {e}"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn form_secrets_are_redacted() {
+        assert_eq!(
+            redact_pairs("link=https%3A%2F%2Fhost.com&password=p%40ss&Token=abc"),
+            "link=https%3A%2F%2Fhost.com&password=%3Credacted%3E&Token=%3Credacted%3E"
+        );
+    }
+
+    #[test]
+    fn json_secrets_are_redacted() {
+        let body = redact_body(r#"{"files":[{"code":"123"}],"access_token":"abc","name":"a"}"#);
+
+        assert_eq!(
+            serde_json::from_str::<Value>(&body).unwrap(),
+            json!({"files": [{"code": REDACTED}], "access_token": REDACTED, "name": "a"})
+        );
+    }
+
+    #[test]
+    fn other_bodies_are_kept() {
+        assert_eq!(redact_body("not json"), "not json");
+    }
+
+    #[test]
+    fn har_stays_valid_after_each_entry() {
+        let path = std::env::temp_dir().join(format!("traffic_cone-{}.har", std::process::id()));
+        let path = path.to_str().unwrap();
+        let read = || serde_json::from_str::<Value>(&std::fs::read_to_string(path).unwrap());
+
+        let mut har = Har::create(path).unwrap();
+        assert_eq!(read().unwrap()["log"]["entries"], json!([]));
+
+        har.append(&json!({"time": 1})).unwrap();
+        har.append(&json!({"time": 2})).unwrap();
+        assert_eq!(
+            read().unwrap()["log"]["entries"],
+            json!([{"time": 1}, {"time": 2}])
+        );
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
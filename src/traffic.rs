@@ -3,10 +3,25 @@ use crate::prelude::*;
 const TRAFFIC_URL: &str = "https://api.real-debrid.com/rest/1.0/traffic";
 const DETAILS_URL: &str = "https://api.real-debrid.com/rest/1.0/traffic/details";
 
-pub fn get_traffic() -> Json {
+/// A `YYYY-MM-DD` date.
+type Day = String;
+
+pub fn get_traffic() -> ApiResult {
     send(Get(""), TRAFFIC_URL)
 }
 
-pub fn get_details() -> Json {
-    send(Get(""), DETAILS_URL)
+/// Usage between `start` and `end`, both included, a week up to today by default.
+///
+/// Without `start`, the period starts a week before `end`.
+pub fn get_details(start: Option<Day>, end: Option<Day>) -> ApiResult {
+    let start = start.or_else(|| crate::date::add_days(end.as_deref()?, -7));
+    let query = [("start", start), ("end", end)]
+        .into_iter()
+        .filter_map(|(name, day)| Some(format!("{name}={}", day?)))
+        .collect::<Vec<_>>();
+
+    match query.is_empty() {
+        true => send(Get(""), DETAILS_URL),
+        false => send(Get(""), format!("{DETAILS_URL}?{}", query.join("&"))),
+    }
 }
@@ -0,0 +1,576 @@
+//! # TUI Module
+//!
+//! This module provides the interactive terminal dashboard.
+//!
+//! The dashboard has four tabs: torrents, downloads, hosts and traffic.
+//! A worker thread performs every API call, refreshing all tabs on
+//! an interval and after each action, so the interface never blocks.
+//!
+//! ## Keybindings
+//!
+//! | Key              | Action                                       |
+//! |------------------|----------------------------------------------|
+//! | `Tab`, `1`-`4`   | Switch tabs                                  |
+//! | `↑`/`↓`, `k`/`j` | Move the selection                           |
+//! | `r`              | Refresh now                                  |
+//! | `a`              | Add a magnet link                            |
+//! | `s`              | Select all files of the selected torrent     |
+//! | `d`              | Delete the selected torrent or download      |
+//! | `u`              | Unrestrict the links of the selected torrent |
+//! | `c`              | Copy the selected links to the clipboard     |
+//! | `q`, `Esc`       | Quit                                         |
+//!
+//! Links are copied with the OSC 52 escape sequence,
+//! which most terminal emulators forward to the system clipboard.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::Duration;
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Paragraph, Row, Table, TableState, Tabs};
+use ratatui::{DefaultTerminal, Frame};
+use serde_json::Value;
+
+use crate::size::humanize;
+use crate::{ApiResult, downloads, hosts, torrents, traffic, unrestrict};
+
+const TAB_TITLES: [&str; 4] = ["Torrents", "Downloads", "Hosts", "Traffic"];
+
+/// How long the interface waits for a key before redrawing.
+const FRAME_TIME: Duration = Duration::from_millis(250);
+
+type Id = String;
+
+/// An action for the worker thread.
+enum Request {
+    Refresh,
+    AddMagnet(String),
+    SelectAllFiles(Id),
+    DeleteTorrent(Id),
+    DeleteDownload(Id),
+    Unrestrict { torrent: Id, links: Vec<String> },
+}
+
+/// A result from the worker thread.
+enum Update {
+    Torrents(Vec<Value>),
+    Downloads(Vec<Value>),
+    Hosts(Vec<(String, Value)>),
+    Traffic(Vec<(String, Value)>),
+    Unrestricted { torrent: Id, links: Vec<String> },
+    Status(String),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Tab {
+    Torrents,
+    Downloads,
+    Hosts,
+    Traffic,
+}
+impl Tab {
+    const ALL: [Tab; 4] = [Tab::Torrents, Tab::Downloads, Tab::Hosts, Tab::Traffic];
+
+    fn index(self) -> usize {
+        Tab::ALL.iter().position(|tab| *tab == self).unwrap_or(0)
+    }
+}
+
+/// What the keyboard currently drives.
+enum Input {
+    Normal,
+    Magnet(String),
+    ConfirmDelete(Request),
+}
+
+struct App {
+    tab: Tab,
+    input: Input,
+    torrents: Vec<Value>,
+    downloads: Vec<Value>,
+    hosts: Vec<(String, Value)>,
+    traffic: Vec<(String, Value)>,
+    /// Unrestricted links per torrent id.
+    unrestricted: HashMap<Id, Vec<String>>,
+    tables: [TableState; 4],
+    status: String,
+    requests: Sender<Request>,
+}
+
+/// Runs the dashboard until the user quits.
+pub fn run(interval: Duration) -> io::Result<()> {
+    let (requests, worker_requests) = mpsc::channel();
+    let (worker_updates, updates) = mpsc::channel();
+    thread::spawn(move || worker(interval, worker_requests, worker_updates));
+
+    let mut app = App {
+        tab: Tab::Torrents,
+        input: Input::Normal,
+        torrents: Vec::new(),
+        downloads: Vec::new(),
+        hosts: Vec::new(),
+        traffic: Vec::new(),
+        unrestricted: HashMap::new(),
+        tables: Default::default(),
+        status: String::from("Loading..."),
+        requests,
+    };
+
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal, &updates);
+    ratatui::restore();
+
+    result
+}
+
+/// Performs API calls on behalf of the interface.
+fn worker(interval: Duration, requests: Receiver<Request>, updates: Sender<Update>) {
+    loop {
+        let request = match requests.recv_timeout(interval) {
+            Ok(request) => request,
+            Err(RecvTimeoutError::Timeout) => Request::Refresh,
+            Err(RecvTimeoutError::Disconnected) => return,
+        };
+
+        let status = match request {
+            Request::Refresh => None,
+            Request::AddMagnet(magnet) => {
+                Some(outcome("Added magnet", torrents::add_magnet(magnet)))
+            }
+            Request::SelectAllFiles(id) => Some(outcome(
+                "Selected all files",
+                torrents::select_files(id, String::from("all")),
+            )),
+            Request::DeleteTorrent(id) => Some(outcome("Deleted torrent", torrents::delete(id))),
+            Request::DeleteDownload(id) => {
+                Some(outcome("Deleted download", downloads::delete_download(id)))
+            }
+            Request::Unrestrict { torrent, links } => {
+                let mut unrestricted = Vec::new();
+                let mut failed = 0;
+                for link in links {
+                    match unrestrict::link(link).map(|body| json(&body)) {
+                        Ok(body) => {
+                            unrestricted.extend(body["download"].as_str().map(String::from))
+                        }
+                        Err(_) => failed += 1,
+                    }
+                }
+
+                let status = format!(
+                    "Unrestricted {} link(s), {failed} failed, press `c` to copy",
+                    unrestricted.len()
+                );
+                let _ = updates.send(Update::Unrestricted {
+                    torrent,
+                    links: unrestricted,
+                });
+                Some(status)
+            }
+        };
+
+        if let Some(status) = status
+            && updates.send(Update::Status(status)).is_err()
+        {
+            return;
+        }
+
+        let refreshed = [
+            torrents::get_torrents().map(|body| Update::Torrents(array(&body))),
+            downloads::get_downloads().map(|body| Update::Downloads(array(&body))),
+            hosts::get_status().map(|body| Update::Hosts(object(&body))),
+            traffic::get_traffic().map(|body| Update::Traffic(object(&body))),
+        ];
+
+        for update in refreshed {
+            let update = update.unwrap_or_else(|e| Update::Status(format!("Refresh failed : {e}")));
+            if updates.send(update).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+fn outcome(success: &str, response: ApiResult) -> String {
+    match response {
+        Ok(_) => success.to_string(),
+        Err(e) => format!("{success} failed : {e}"),
+    }
+}
+
+fn json(body: &str) -> Value {
+    serde_json::from_str(body).unwrap_or_default()
+}
+
+fn array(body: &str) -> Vec<Value> {
+    match json(body) {
+        Value::Array(values) => values,
+        _ => Vec::new(),
+    }
+}
+
+/// Reads an object keyed by host, sorted by host.
+fn object(body: &str) -> Vec<(String, Value)> {
+    let mut entries = match json(body) {
+        Value::Object(map) => map.into_iter().collect::<Vec<_>>(),
+        _ => Vec::new(),
+    };
+    entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    entries
+}
+
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Null => String::new(),
+        value => value.to_string(),
+    }
+}
+
+/// Renders a progress percentage as a bar, like `[#####.....]  50%`.
+fn progress_bar(progress: f64) -> String {
+    let filled = ((progress / 10.0).round() as usize).min(10);
+
+    format!(
+        "[{}{}] {progress:>3.0}%",
+        "#".repeat(filled),
+        ".".repeat(10 - filled)
+    )
+}
+
+/// Copies `text` to the clipboard through the terminal.
+fn copy_to_clipboard(text: &str) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+    stdout.flush()
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    bytes
+        .chunks(3)
+        .flat_map(|chunk| {
+            let triple = chunk.iter().enumerate().fold(0u32, |triple, (i, byte)| {
+                triple | u32::from(*byte) << (16 - 8 * i)
+            });
+
+            (0..4).map(move |i| match i <= chunk.len() {
+                true => ALPHABET[(triple >> (18 - 6 * i) & 0x3f) as usize] as char,
+                false => '=',
+            })
+        })
+        .collect()
+}
+
+impl App {
+    fn run(
+        &mut self,
+        terminal: &mut DefaultTerminal,
+        updates: &Receiver<Update>,
+    ) -> io::Result<()> {
+        loop {
+            while let Ok(update) = updates.try_recv() {
+                self.apply(update);
+            }
+
+            terminal.draw(|frame| self.draw(frame))?;
+
+            if !event::poll(FRAME_TIME)? {
+                continue;
+            }
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+
+            let input = std::mem::replace(&mut self.input, Input::Normal);
+            self.input = match input {
+                Input::Normal => match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                    code => self.on_key(code),
+                },
+                Input::Magnet(mut magnet) => match key.code {
+                    KeyCode::Enter if !magnet.is_empty() => {
+                        self.send(Request::AddMagnet(magnet));
+                        Input::Normal
+                    }
+                    KeyCode::Esc => Input::Normal,
+                    KeyCode::Backspace => {
+                        magnet.pop();
+                        Input::Magnet(magnet)
+                    }
+                    KeyCode::Char(c) => {
+                        magnet.push(c);
+                        Input::Magnet(magnet)
+                    }
+                    _ => Input::Magnet(magnet),
+                },
+                Input::ConfirmDelete(request) => {
+                    match key.code {
+                        KeyCode::Char('y') => self.send(request),
+                        _ => self.status = String::from("Deletion cancelled"),
+                    }
+                    Input::Normal
+                }
+            };
+        }
+    }
+
+    fn apply(&mut self, update: Update) {
+        match update {
+            Update::Torrents(torrents) => self.torrents = torrents,
+            Update::Downloads(downloads) => self.downloads = downloads,
+            Update::Hosts(hosts) => self.hosts = hosts,
+            Update::Traffic(traffic) => self.traffic = traffic,
+            Update::Unrestricted { torrent, links } => {
+                self.unrestricted.insert(torrent, links);
+            }
+            Update::Status(status) => self.status = status,
+        }
+
+        if self.status == "Loading..." {
+            self.status = String::from("Press `q` to quit");
+        }
+
+        self.clamp_selection();
+    }
+
+    fn send(&mut self, request: Request) {
+        self.status = String::from("Working...");
+        let _ = self.requests.send(request);
+    }
+
+    fn rows(&self) -> usize {
+        match self.tab {
+            Tab::Torrents => self.torrents.len(),
+            Tab::Downloads => self.downloads.len(),
+            Tab::Hosts => self.hosts.len(),
+            Tab::Traffic => self.traffic.len(),
+        }
+    }
+
+    fn selected_torrent(&self) -> Option<&Value> {
+        let index = self.tables[Tab::Torrents.index()].selected()?;
+        self.torrents
+            .get(index)
+            .filter(|_| self.tab == Tab::Torrents)
+    }
+
+    fn selected_download(&self) -> Option<&Value> {
+        let index = self.tables[Tab::Downloads.index()].selected()?;
+        self.downloads
+            .get(index)
+            .filter(|_| self.tab == Tab::Downloads)
+    }
+
+    fn on_key(&mut self, code: KeyCode) -> Input {
+        let tab = self.tab.index();
+
+        match code {
+            KeyCode::Tab | KeyCode::Right => self.tab = Tab::ALL[(self.tab.index() + 1) % 4],
+            KeyCode::BackTab | KeyCode::Left => self.tab = Tab::ALL[(self.tab.index() + 3) % 4],
+            KeyCode::Char(c @ '1'..='4') => self.tab = Tab::ALL[c as usize - '1' as usize],
+            KeyCode::Down | KeyCode::Char('j') => self.tables[tab].select_next(),
+            KeyCode::Up | KeyCode::Char('k') => self.tables[tab].select_previous(),
+            KeyCode::Char('r') => self.send(Request::Refresh),
+            KeyCode::Char('a') => return Input::Magnet(String::new()),
+            KeyCode::Char('s') => {
+                if let Some(id) = self.selected_torrent().map(|torrent| text(&torrent["id"])) {
+                    self.send(Request::SelectAllFiles(id));
+                }
+            }
+            KeyCode::Char('d') => {
+                let request = match (self.selected_torrent(), self.selected_download()) {
+                    (Some(torrent), _) => Request::DeleteTorrent(text(&torrent["id"])),
+                    (_, Some(download)) => Request::DeleteDownload(text(&download["id"])),
+                    _ => return Input::Normal,
+                };
+                self.status = String::from("Delete the selected item? [y/N]");
+                return Input::ConfirmDelete(request);
+            }
+            KeyCode::Char('u') => {
+                if let Some(torrent) = self.selected_torrent() {
+                    let request = Request::Unrestrict {
+                        torrent: text(&torrent["id"]),
+                        links: torrent["links"]
+                            .as_array()
+                            .into_iter()
+                            .flatten()
+                            .map(text)
+                            .collect(),
+                    };
+                    self.send(request);
+                }
+            }
+            KeyCode::Char('c') => {
+                let links = match (self.selected_torrent(), self.selected_download()) {
+                    (Some(torrent), _) => self
+                        .unrestricted
+                        .get(&text(&torrent["id"]))
+                        .cloned()
+                        .unwrap_or_else(|| {
+                            torrent["links"]
+                                .as_array()
+                                .into_iter()
+                                .flatten()
+                                .map(text)
+                                .collect()
+                        }),
+                    (_, Some(download)) => vec![text(&download["download"])],
+                    _ => Vec::new(),
+                };
+
+                self.status = match copy_to_clipboard(&links.join("\n")) {
+                    Ok(()) => format!("Copied {} link(s)", links.len()),
+                    Err(e) => format!("Copy failed : {e}"),
+                };
+            }
+            _ => (),
+        }
+
+        self.clamp_selection();
+
+        Input::Normal
+    }
+
+    /// Keeps the selection of the current tab on an existing row.
+    fn clamp_selection(&mut self) {
+        let rows = self.rows();
+        let table = &mut self.tables[self.tab.index()];
+
+        match table.selected() {
+            None if rows > 0 => table.select(Some(0)),
+            Some(selected) if selected >= rows => table.select(rows.checked_sub(1)),
+            _ => (),
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [tabs_area, table_area, status_area] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let tabs = Tabs::new(TAB_TITLES)
+            .block(Block::bordered().title(" traffic_cone "))
+            .select(self.tab.index())
+            .highlight_style(Style::new().bold().reversed());
+        frame.render_widget(tabs, tabs_area);
+
+        let (header, widths, rows): (Vec<&str>, Vec<Constraint>, Vec<Row>) = match self.tab {
+            Tab::Torrents => (
+                vec!["Filename", "Status", "Progress", "Size", "Added"],
+                vec![
+                    Constraint::Fill(1),
+                    Constraint::Length(20),
+                    Constraint::Length(17),
+                    Constraint::Length(10),
+                    Constraint::Length(10),
+                ],
+                self.torrents
+                    .iter()
+                    .map(|torrent| {
+                        Row::new([
+                            text(&torrent["filename"]),
+                            text(&torrent["status"]),
+                            progress_bar(torrent["progress"].as_f64().unwrap_or(0.0)),
+                            humanize(torrent["bytes"].as_u64().unwrap_or(0)),
+                            text(&torrent["added"]).chars().take(10).collect(),
+                        ])
+                    })
+                    .collect(),
+            ),
+            Tab::Downloads => (
+                vec!["Filename", "Host", "Size", "Generated"],
+                vec![
+                    Constraint::Fill(1),
+                    Constraint::Length(20),
+                    Constraint::Length(10),
+                    Constraint::Length(10),
+                ],
+                self.downloads
+                    .iter()
+                    .map(|download| {
+                        Row::new([
+                            text(&download["filename"]),
+                            text(&download["host"]),
+                            humanize(download["filesize"].as_u64().unwrap_or(0)),
+                            text(&download["generated"]).chars().take(10).collect(),
+                        ])
+                    })
+                    .collect(),
+            ),
+            Tab::Hosts => (
+                vec!["Host", "Name", "Status", "Supported"],
+                vec![
+                    Constraint::Fill(1),
+                    Constraint::Fill(1),
+                    Constraint::Length(10),
+                    Constraint::Length(10),
+                ],
+                self.hosts
+                    .iter()
+                    .map(|(host, status)| {
+                        Row::new([
+                            host.clone(),
+                            text(&status["name"]),
+                            text(&status["status"]),
+                            text(&status["supported"]),
+                        ])
+                    })
+                    .collect(),
+            ),
+            Tab::Traffic => (
+                vec!["Host", "Type", "Used", "Limit", "Left", "Reset"],
+                vec![
+                    Constraint::Fill(1),
+                    Constraint::Length(8),
+                    Constraint::Length(10),
+                    Constraint::Length(10),
+                    Constraint::Length(10),
+                    Constraint::Length(8),
+                ],
+                self.traffic
+                    .iter()
+                    .map(|(host, usage)| {
+                        Row::new([
+                            host.clone(),
+                            text(&usage["type"]),
+                            humanize(usage["bytes"].as_u64().unwrap_or(0)),
+                            text(&usage["limit"]),
+                            text(&usage["left"]),
+                            text(&usage["reset"]),
+                        ])
+                    })
+                    .collect(),
+            ),
+        };
+
+        let table = Table::new(rows, widths)
+            .header(Row::new(header).style(Style::new().bold()))
+            .block(Block::bordered())
+            .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(table, table_area, &mut self.tables[self.tab.index()]);
+
+        let status = match &self.input {
+            Input::Magnet(magnet) => Line::from(format!("Magnet: {magnet}_")),
+            _ => Line::from(format!(
+                "{}  |  r refresh  a add  s select  d delete  u unrestrict  c copy  q quit",
+                self.status
+            )),
+        };
+        frame.render_widget(Paragraph::new(status), status_area);
+    }
+}
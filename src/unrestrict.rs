@@ -1,3 +1,5 @@
+use serde_json::json;
+
 use crate::prelude::*;
 
 const CHECK_URL: &str = "https://api.real-debrid.com/rest/1.0/unrestrict/check";
@@ -6,30 +8,67 @@ const FOLDER_URL: &str = "https://api.real-debrid.com/rest/1.0/unrestrict/folder
 const CONTAINER_FILE_URL: &str = "https://api.real-debrid.com/rest/1.0/unrestrict/containerFile";
 const CONTAINER_LINK_URL: &str = "https://api.real-debrid.com/rest/1.0/unrestrict/containerLink";
 
-pub fn check(link: String) -> Json {
+pub fn check(link: String) -> ApiResult {
     let body = format!("{} \"link\": \"{}\" {}", '{', link, '}');
 
     send(Post(body), CHECK_URL)
 }
 
-pub fn link(link: String) -> Json {
-    let body = format!("{} \"link\": \"{}\" {}", '{', link, '}');
+pub fn link(link: String) -> ApiResult {
+    link_with(link, None, false)
+}
+
+/// Unrestricts a link protected by `password`, using remote traffic with `remote`.
+pub fn link_with(link: String, password: Option<&str>, remote: bool) -> ApiResult {
+    send(Post(link_body(&link, password, remote)), LINK_URL)
+}
+
+fn link_body(link: &str, password: Option<&str>, remote: bool) -> String {
+    let mut body = json!({ "link": link });
+    if let Some(password) = password {
+        body["password"] = json!(password);
+    }
+    if remote {
+        body["remote"] = json!("1");
+    }
 
-    send(Post(body), LINK_URL)
+    body.to_string()
 }
 
-pub fn folder(link: String) -> Json {
+pub fn folder(link: String) -> ApiResult {
     let body = format!("{} \"link\": \"{}\" {}", '{', link, '}');
 
     send(Post(body), FOLDER_URL)
 }
 
-pub fn container_file() -> Json {
+pub fn container_file() -> ApiResult {
     send(Put(""), CONTAINER_FILE_URL)
 }
 
-pub fn container_link(link: String) -> Json {
+pub fn container_link(link: String) -> ApiResult {
     let body = format!("{} \"link\": \"{}\" {}", '{', link, '}');
 
     send(Post(body), CONTAINER_LINK_URL)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn link_body_keeps_escaped_passwords() {
+        let body = link_body("https://host.com/f/1?a=1&b=2", Some(r#"p"a\s&s"#), true);
+        let fields = crate::form_fields(&body);
+
+        assert_eq!(fields["link"], "https://host.com/f/1?a=1&b=2");
+        assert_eq!(fields["password"], r#"p"a\s&s"#);
+        assert_eq!(fields["remote"], "1");
+    }
+
+    #[test]
+    fn link_body_omits_unset_options() {
+        let fields = crate::form_fields(&link_body("https://host.com/f/1", None, false));
+
+        assert_eq!(fields.len(), 1);
+    }
+}
@@ -0,0 +1,211 @@
+//! # Usage Module
+//!
+//! This module aggregates the traffic details of a period into reports.
+//!
+//! The details hold, for every day, the bytes used on each host.
+//! They are summed per host, per day, per ISO week, or into a single total,
+//! and rendered as a table with humanized sizes, or as CSV with sizes in bytes.
+
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+
+use crate::app::{Aggregate, Output};
+use crate::date;
+use crate::error::{ApiError, Failure};
+use crate::prelude::*;
+use crate::size::humanize;
+use crate::{ARGS, traffic};
+
+/// A row of a report.
+struct Row {
+    key: String,
+    bytes: u64,
+}
+
+/// Reports the usage between `start` and `end`, aggregated `by`.
+///
+/// With `top`, only the rows using the most bytes are kept.
+/// With `--output json`, the raw traffic details are returned instead.
+pub fn details(
+    start: Option<String>,
+    end: Option<String>,
+    by: Aggregate,
+    top: Option<usize>,
+    csv: bool,
+) -> ApiResult {
+    let response = traffic::get_details(start, end)?;
+    if *ARGS.output() == Output::Json {
+        return Ok(response);
+    }
+
+    let details = serde_json::from_str::<Value>(&response).map_err(|e| {
+        ApiError::new(
+            Failure::Api,
+            format!("traffic details : invalid response : {e}"),
+        )
+    })?;
+    let (mut rows, total) = aggregate(&details, by);
+
+    if let Some(top) = top {
+        rows.sort_by_key(|row| Reverse(row.bytes));
+        rows.truncate(top);
+    }
+
+    let heading = match by {
+        Aggregate::Host => "host",
+        Aggregate::Day => "day",
+        Aggregate::Week => "week",
+        Aggregate::Total => "period",
+    };
+
+    Ok(match csv {
+        true => render_csv(heading, &rows, total),
+        false => render_table(heading, &rows, total),
+    })
+}
+
+/// Sums the bytes of every day into rows, in the order of their keys,
+/// or by decreasing usage for hosts, along with the total.
+///
+/// Totals have no rows, as the total row follows every report.
+fn aggregate(details: &Value, by: Aggregate) -> (Vec<Row>, u64) {
+    let mut sums = BTreeMap::<String, u64>::new();
+    let mut total = 0;
+
+    for (day, usage) in details.as_object().into_iter().flatten() {
+        let hosts = usage["host"].as_object();
+        let bytes = usage["bytes"].as_u64().unwrap_or_else(|| {
+            hosts
+                .into_iter()
+                .flatten()
+                .filter_map(|(_, bytes)| bytes.as_u64())
+                .sum()
+        });
+        total += bytes;
+
+        match by {
+            Aggregate::Host => {
+                for (host, bytes) in hosts.into_iter().flatten() {
+                    *sums.entry(host.clone()).or_default() += bytes.as_u64().unwrap_or(0);
+                }
+            }
+            Aggregate::Day => *sums.entry(day.clone()).or_default() += bytes,
+            Aggregate::Week => {
+                let Some(seconds) = date::parse_rfc3339(day) else {
+                    warn!("traffic details : invalid day `{day}`");
+                    continue;
+                };
+                let (year, week) = date::iso_week(seconds.div_euclid(date::SECONDS_PER_DAY));
+                *sums.entry(format!("{year}-W{week:02}")).or_default() += bytes;
+            }
+            Aggregate::Total => {}
+        }
+    }
+
+    let mut rows = sums
+        .into_iter()
+        .map(|(key, bytes)| Row { key, bytes })
+        .collect::<Vec<_>>();
+    if by == Aggregate::Host {
+        rows.sort_by_key(|row| Reverse(row.bytes));
+    }
+
+    (rows, total)
+}
+
+fn render_table(heading: &str, rows: &[Row], total: u64) -> String {
+    let width = rows
+        .iter()
+        .map(|row| row.key.len())
+        .chain([heading.len(), "total".len()])
+        .max()
+        .unwrap_or_default();
+
+    let share = |bytes: u64| match total {
+        0 => 0.0,
+        total => bytes as f64 * 100.0 / total as f64,
+    };
+
+    let mut lines = vec![format!("{heading:<width$}  {:>10}  {:>6}", "size", "share")];
+    lines.extend(rows.iter().map(|row| {
+        format!(
+            "{:<width$}  {:>10}  {:>5.1}%",
+            row.key,
+            humanize(row.bytes),
+            share(row.bytes)
+        )
+    }));
+    lines.push(format!(
+        "{:<width$}  {:>10}  {:>5.1}%",
+        "total",
+        humanize(total),
+        share(total)
+    ));
+
+    lines.join("\n")
+}
+
+fn render_csv(heading: &str, rows: &[Row], total: u64) -> String {
+    let mut lines = vec![format!("{heading},bytes")];
+    lines.extend(
+        rows.iter()
+            .map(|row| format!("{},{}", csv_field(&row.key), row.bytes)),
+    );
+    lines.push(format!("total,{total}"));
+
+    lines.join("\n")
+}
+
+fn csv_field(field: &str) -> String {
+    match field.contains([',', '"', '\n']) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn details() -> Value {
+        json!({
+            "2024-12-29": {"host": {"a.com": 100, "b.com": 300}, "bytes": 400},
+            "2024-12-30": {"host": {"a.com": 500}},
+            "2024-12-31": {"host": {"b.com": 50}, "bytes": 50},
+        })
+    }
+
+    fn sums(rows: &[Row]) -> Vec<(&str, u64)> {
+        rows.iter()
+            .map(|row| (row.key.as_str(), row.bytes))
+            .collect()
+    }
+
+    #[test]
+    fn hosts_are_sorted_by_usage() {
+        let (rows, total) = aggregate(&details(), Aggregate::Host);
+
+        assert_eq!(sums(&rows), [("a.com", 600), ("b.com", 350)]);
+        assert_eq!(total, 950);
+    }
+
+    #[test]
+    fn weeks_follow_iso_years() {
+        let (rows, total) = aggregate(&details(), Aggregate::Week);
+
+        assert_eq!(sums(&rows), [("2024-W52", 400), ("2025-W01", 550)]);
+        assert_eq!(total, 950);
+    }
+
+    #[test]
+    fn totals_have_no_rows() {
+        let (rows, total) = aggregate(&details(), Aggregate::Total);
+
+        assert!(rows.is_empty());
+        assert_eq!(total, 950);
+    }
+}